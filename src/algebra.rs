@@ -1,32 +1,36 @@
+use std::fmt::Debug;
 use std::ops;
-use rand::Rng;
-use serde_derive::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize as SerializeTrait, Serializer};
+use serde::de::{DeserializeOwned, Error as DeError};
+use crate::extension::QuadraticExtensible;
+use crate::hasher::{KeccakSponge, Sponge};
+use rand::RngCore;
+use rand::rngs::OsRng;
 
-const P:u128 = 1 + 407 * ( 1 << 119 );
+pub const P:u128 = 1 + 407 * ( 1 << 119 );
 
 
-#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+#[derive(Debug, Copy, Clone)]
 pub struct FieldElement {
     pub value: u128,
-    prime: u128,
 }
 
 impl FieldElement {
 
     pub fn new(n:u128) -> FieldElement {
-        return FieldElement{value: n % P, prime: P}
+        FieldElement{value: n % P}
     }
     
     pub fn zero() -> FieldElement {
-        return FieldElement{value: 0, prime: P}
+        FieldElement{value: 0}
     }
     
     pub fn one() -> FieldElement {
-        return FieldElement{value: 1, prime: P}
+        FieldElement{value: 1}
     }
     
     pub fn generator() -> FieldElement {
-        return FieldElement::new(85408008396924667383611388730472331217);
+        FieldElement::new(85408008396924667383611388730472331217)
     }
     
     pub fn primitive_nth_root(&self, n:u128) -> FieldElement {
@@ -37,32 +41,223 @@ impl FieldElement {
         
         while order != n {
             root = root ^ 2;
-            order = order / 2;
+            order /= 2;
         }
         root
     }
     
     pub fn sample(byte_array: &[u8;32]) -> FieldElement {
-        let mut acc = FieldElement::one();
+        let mut acc = FieldElement::zero();
         
-        for b in 0..byte_array.len() {
+        for b in byte_array.iter() {
             
-            acc = (acc * FieldElement::new(256)) ^ (byte_array[b] as u128);
+            acc = (acc * FieldElement::new(256)) + FieldElement::new(*b as u128);
         }
         acc
     }
     
     pub fn is_zero(&self) -> bool {
-        return self.value == 0;
+        self.value == 0
     }
     
     pub fn inverse(self) -> FieldElement {
         let a = inv(self.value);
         assert!(FieldElement::new(a) * self == FieldElement::one());
-        return FieldElement::new(a);
+        FieldElement::new(a)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 16] {
+        self.value.to_be_bytes()
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 16] {
+        self.value.to_le_bytes()
+    }
+
+    // None if the bytes encode a value >= P
+    pub fn from_be_bytes(bytes: &[u8; 16]) -> Option<FieldElement> {
+        let value = u128::from_be_bytes(*bytes);
+        if value >= P {
+            return None;
+        }
+        Some(FieldElement{value})
+    }
+
+    pub fn from_le_bytes(bytes: &[u8; 16]) -> Option<FieldElement> {
+        let value = u128::from_le_bytes(*bytes);
+        if value >= P {
+            return None;
+        }
+        Some(FieldElement{value})
+    }
+
+    // inverts all values with a single field inversion, zeros are mapped to zero
+    pub fn batch_inverse(values: &[FieldElement]) -> Vec<FieldElement> {
+        <FieldElement as Field>::batch_inverse(values)
     }
 }
 
+pub trait Field: Debug + Copy + Clone + PartialEq + SerializeTrait + DeserializeOwned
+    + ops::Add<Output = Self> + ops::Sub<Output = Self> + ops::Mul<Output = Self>
+    + ops::Div<Output = Self> + ops::Neg<Output = Self> + ops::BitXor<u128, Output = Self> {
+
+    // number of bits in the modulus
+    const BITS: u32;
+
+    // largest k such that 2^k divides the order of the multiplicative group
+    const TWO_ADICITY: u32;
+
+    // identifies the field in serialized proofs, must be unique per field
+    const ID: u8;
+
+    fn new(n: u128) -> Self;
+
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    // generator of the subgroup of order 2^TWO_ADICITY
+    fn generator() -> Self;
+
+    fn inverse(self) -> Self;
+
+    fn is_zero(&self) -> bool;
+
+    // draws an element from a Fiat-Shamir sponge, extension fields squeeze once per coefficient
+    fn sample<S: Sponge>(sponge: &mut S) -> Self;
+
+    // canonical fixed-width little-endian encoding, the same bytes as the serialization
+    fn to_bytes(self) -> Vec<u8>;
+
+    // inverse of to_bytes, None unless bytes is the canonical encoding of an element
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+
+    // coordinates over the 128-bit prime field, lets hashes over that field absorb elements
+    // natively instead of through their encoding, None for fields over another prime
+    fn prime_coordinates(self) -> Option<Vec<FieldElement>> {
+        None
+    }
+
+    // Montgomery's trick: one inversion plus 3(n-1) multiplications, zeros are mapped to zero
+    fn batch_inverse(values: &[Self]) -> Vec<Self> {
+
+        // prefix products of the nonzero values
+        let mut prefix: Vec<Self> = Vec::with_capacity(values.len());
+        let mut acc = Self::one();
+        for v in values.iter() {
+            prefix.push(acc);
+            if !v.is_zero() {
+                acc = acc * *v;
+            }
+        }
+
+        let mut inv_acc = acc.inverse();
+        let mut inverses = vec![Self::zero(); values.len()];
+        for i in (0..values.len()).rev() {
+            if values[i].is_zero() {
+                continue;
+            }
+            inverses[i] = inv_acc * prefix[i];
+            inv_acc = inv_acc * values[i];
+        }
+        inverses
+    }
+
+    fn primitive_nth_root(n: u128) -> Self {
+
+        assert!(n <= 1 << Self::TWO_ADICITY && (n & (n-1)) == 0);
+        let mut root = Self::generator();
+        let mut order: u128 = 1 << Self::TWO_ADICITY;
+
+        while order != n {
+            root = root ^ 2;
+            order /= 2;
+        }
+        root
+    }
+}
+
+// fields of prime order p
+pub trait PrimeField: Field {
+
+    const MODULUS: u128;
+}
+
+impl Field for FieldElement {
+
+    const BITS: u32 = 128 - P.leading_zeros();
+
+    const TWO_ADICITY: u32 = 119;
+
+    const ID: u8 = 1;
+
+    fn new(n: u128) -> Self {
+        FieldElement::new(n)
+    }
+
+    fn zero() -> Self {
+        FieldElement::zero()
+    }
+
+    fn one() -> Self {
+        FieldElement::one()
+    }
+
+    fn generator() -> Self {
+        FieldElement::generator()
+    }
+
+    fn inverse(self) -> Self {
+        FieldElement::inverse(self)
+    }
+
+    fn is_zero(&self) -> bool {
+        FieldElement::is_zero(self)
+    }
+
+    fn sample<S: Sponge>(sponge: &mut S) -> Self {
+        FieldElement::sample(&sponge.squeeze())
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        FieldElement::from_le_bytes(bytes.try_into().ok()?)
+    }
+
+    fn prime_coordinates(self) -> Option<Vec<FieldElement>> {
+        Some(vec![self])
+    }
+}
+
+// proofs carry the little-endian encoding, decoding rejects values outside [0, P)
+impl SerializeTrait for FieldElement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_le_bytes().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FieldElement, D::Error> {
+        let bytes = <[u8; 16]>::deserialize(deserializer)?;
+        FieldElement::from_le_bytes(&bytes).ok_or_else(|| D::Error::custom("field element is not reduced"))
+    }
+}
+
+impl PrimeField for FieldElement {
+
+    const MODULUS: u128 = P;
+}
+
+impl QuadraticExtensible for FieldElement {
+
+    const QUADRATIC_NON_RESIDUE: u128 = 3;
+
+    const QUADRATIC_GENERATOR: u128 = 15662637576412140028812096168348292387;
+}
+
 impl PartialEq<FieldElement> for FieldElement {
 
     fn eq(&self, other: &Self) -> bool {
@@ -80,9 +275,9 @@ impl ops::Add<FieldElement> for FieldElement {
         let a = P - self.value;
         
         if a <= rhs.value {
-            return FieldElement::new(rhs.value - a);
+            FieldElement::new(rhs.value - a)
         } else {
-            return FieldElement::new(self.value + rhs.value);
+            FieldElement::new(self.value + rhs.value)
         }
     }
 }
@@ -90,62 +285,16 @@ impl ops::Add<FieldElement> for FieldElement {
 impl ops::Mul<FieldElement> for FieldElement {
     type Output = FieldElement;
     
-    //https://stackoverflow.com/questions/12168348/ways-to-do-modulo-multiplication-with-primitive-types
     fn mul(self, rhs: FieldElement) -> FieldElement {
-        
-        let mut a = self.value;
-        let mut b = rhs.value;
-        let mut res: u128 = 0;
-        let mut temp: u128 = 0;
-        
-        while a != 0 {
-            if ( (a & 1) != 0) {
-                
-                if (b >= P.wrapping_sub(res) ) {
-                    res = res.wrapping_sub(P);
-                }
-                res = res.wrapping_add(b);
-            }
-            a >>= 1;
-            
-            temp = b;
-            if ( b >= P.wrapping_sub(b)) {
-                temp = temp.wrapping_sub(P);
-            }
-            b = b.wrapping_add(temp);
-        }
-        return FieldElement::new(res);
+        FieldElement{value: mul_mod(self.value, rhs.value)}
     }
 }
 
 impl ops::Mul<FieldElement> for &FieldElement {
     type Output = FieldElement;
     
-    //https://stackoverflow.com/questions/12168348/ways-to-do-modulo-multiplication-with-primitive-types
     fn mul(self, rhs: FieldElement) -> FieldElement {
-        
-        let mut a = self.value;
-        let mut b = rhs.value;
-        let mut res: u128 = 0;
-        let mut temp: u128 = 0;
-        
-        while a != 0 {
-            if ( (a & 1) != 0) {
-                
-                if (b >= P.wrapping_sub(res) ) {
-                    res = res.wrapping_sub(P);
-                }
-                res = res.wrapping_add(b);
-            }
-            a >>= 1;
-            
-            temp = b;
-            if ( b >= P.wrapping_sub(b)) {
-                temp = temp.wrapping_sub(P);
-            }
-            b = b.wrapping_add(temp);
-        }
-        return FieldElement::new(res);
+        FieldElement{value: mul_mod(self.value, rhs.value)}
     }
 }
 
@@ -158,11 +307,11 @@ impl ops::Sub<FieldElement> for FieldElement {
         
             let a = rhs.value - self.value;
             
-            return FieldElement::new(P-a);
+            FieldElement::new(P-a)
             
         } else {
             
-            return FieldElement::new(self.value - rhs.value);
+            FieldElement::new(self.value - rhs.value)
         }
     }
 }
@@ -171,7 +320,7 @@ impl ops::Neg for FieldElement {
     type Output = FieldElement;
     
     fn neg(self) -> FieldElement {
-        return FieldElement::new(P-self.value);
+        FieldElement::new(P-self.value)
     }
 }
 
@@ -183,30 +332,124 @@ impl ops::BitXor<u128> for FieldElement {
         let mut acc = FieldElement::one();
         
         for i in (0.. format!("{rhs:b}").chars().count()).rev() {
-            let mut val = FieldElement::new(self.value);
+            let val = FieldElement::new(self.value);
             let acc2 = FieldElement::new(acc.value);
             acc = acc * acc2;
-            if (1 << i != 0) && rhs != 0 {
+            if (1 << i) & rhs != 0 {
                 acc = acc * val;
             }
         }
         
-        return acc;
+        acc
     }
 }
 
 impl ops::Div for FieldElement {
     type Output = FieldElement;
     
+    // division is multiplication by the inverse
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn div(self, rhs: FieldElement) -> FieldElement {
         
         let a  = FieldElement::new( inv(rhs.value) );
-        return self * a;
+        self * a
     } 
 }
 
 
 
+// 2^256 mod P, used to leave Montgomery form after the first reduction
+const R2: u128 = r2();
+
+const fn r2() -> u128 {
+    // 2^128 mod P, then doubled 128 times
+    let mut x = 0_u128.wrapping_sub(P);
+    let mut i = 0;
+    while i < 128 {
+        x = if x >= P - x { x - (P - x) } else { x + x };
+        i += 1;
+    }
+    x
+}
+
+// full 256-bit product as (low, high)
+#[inline]
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    let (a0, a1) = (a as u64 as u128, a >> 64);
+    let (b0, b1) = (b as u64 as u128, b >> 64);
+
+    let lo = a0 * b0;
+    let mid0 = a0 * b1;
+    let mid1 = a1 * b0;
+    let hi = a1 * b1;
+
+    let (mid, mid_carry) = mid0.overflowing_add(mid1);
+    let (lo, lo_carry) = lo.overflowing_add(mid << 64);
+    let hi = hi + (mid >> 64) + ((mid_carry as u128) << 64) + lo_carry as u128;
+
+    (lo, hi)
+}
+
+// Montgomery reduction: (hi * 2^128 + lo) / 2^128 mod P, for inputs below P * 2^128.
+// P = 1 + 407 * 2^119 turns both products of the reduction into shifts and small multiplies
+#[inline]
+fn montgomery_reduce(lo: u128, hi: u128) -> u128 {
+    // lo * -P^-1 mod 2^128. P is 1 mod 2^119, so P^-1 = 1 - 407 * 2^119 mod 2^128
+    let m = (lo.wrapping_mul(407) << 119).wrapping_sub(lo);
+
+    // high half of m * P = m + m * 407 * 2^119, where m * 407 < 2^137
+    let k_lo = m.wrapping_mul(407);
+    let k_hi = ((m >> 64) * 407 + (((m as u64 as u128) * 407) >> 64)) >> 64;
+    let (_, mp_carry) = (k_lo << 119).overflowing_add(m);
+    let mp_hi = ((k_hi << 119) | (k_lo >> 9)) + mp_carry as u128;
+
+    // lo + m * P is 0 mod 2^128 and carries exactly when lo is nonzero
+    let carry = (lo != 0) as u128;
+    let (t, overflow_a) = hi.overflowing_add(mp_hi);
+    let (t, overflow_b) = t.overflowing_add(carry);
+
+    if overflow_a || overflow_b || t >= P {
+        return t.wrapping_sub(P);
+    }
+    t
+}
+
+// a * b mod P for canonical a and b
+#[inline]
+fn mul_mod(a: u128, b: u128) -> u128 {
+    let (lo, hi) = mul_wide(a, b);
+    let (lo, hi) = mul_wide(montgomery_reduce(lo, hi), R2);
+    montgomery_reduce(lo, hi)
+}
+
+//https://stackoverflow.com/questions/12168348/ways-to-do-modulo-multiplication-with-primitive-types
+#[cfg(test)]
+fn mul_double_and_add(a: u128, b: u128) -> u128 {
+    
+    let mut a = a;
+    let mut b = b;
+    let mut res: u128 = 0;
+    
+    while a != 0 {
+        if (a & 1) != 0 {
+            
+            if b >= P.wrapping_sub(res) {
+                res = res.wrapping_sub(P);
+            }
+            res = res.wrapping_add(b);
+        }
+        a >>= 1;
+        
+        let mut temp = b;
+        if b >= P.wrapping_sub(b) {
+            temp = temp.wrapping_sub(P);
+        }
+        b = b.wrapping_add(temp);
+    }
+    res
+}
+
+
 //https://github.com/facebook/winterfell/blob/main/math/src/field/f128/mod.rs
 fn sub_192x192(a0: u64, a1: u64, a2: u64, b0: u64, b1: u64, b2: u64) -> (u64, u64, u64) {
     let z0 = (a0 as u128).wrapping_sub(b0 as u128);
@@ -316,3 +559,188 @@ pub fn inv(x: u128) -> u128 {
 
     a
 }
+
+// uniform element from OS randomness, for prover randomizers, keys and tests
+pub fn random_element<F: Field>() -> F {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let mut sponge = KeccakSponge::new();
+    sponge.absorb(&bytes);
+    F::sample(&mut sponge)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // field axioms and root of unity structure shared by every Field implementation
+    pub fn check_field_properties<F: Field>() {
+
+        let zero = F::zero();
+        let one = F::one();
+
+        for _ in 0..100 {
+            let a = random_element::<F>();
+            let b = random_element::<F>();
+            let c = random_element::<F>();
+
+            assert!(a + b == b + a, "addition is not commutative");
+            assert!(a * b == b * a, "multiplication is not commutative");
+            assert!((a + b) + c == a + (b + c), "addition is not associative");
+            assert!((a * b) * c == a * (b * c), "multiplication is not associative");
+            assert!(a * (b + c) == a * b + a * c, "multiplication does not distribute over addition");
+
+            assert!(a + zero == a && a * one == a && a * zero == zero, "identities fail");
+            assert!(a - b == a + (-b) && a + (-a) == zero && (a - b) + b == a, "additive inverses fail");
+
+            if !a.is_zero() {
+                assert!(a * a.inverse() == one, "multiplicative inverse fails");
+                assert!((b / a) * a == b, "division fails");
+            }
+
+            assert!(a ^ 0 == one && a ^ 1 == a && a ^ 5 == a * a * a * a * a, "exponentiation fails");
+        }
+
+        assert!(-zero == zero, "negation of zero fails");
+        assert!(F::new(7) == F::new(3) + F::new(4), "embedding of integers fails");
+
+        for log_n in 0..F::TWO_ADICITY + 1 {
+            let n: u128 = 1 << log_n;
+            let root = F::primitive_nth_root(n);
+            assert!(root ^ n == one, "root of unity has wrong order");
+            if log_n > 0 {
+                assert!(root ^ (n / 2) == -one, "root of unity is not primitive");
+            }
+        }
+
+        let (mut first, mut second) = (KeccakSponge::new(), KeccakSponge::new());
+        first.absorb(&[7u8; 32]);
+        second.absorb(&[7u8; 32]);
+        let sample = F::sample(&mut first);
+        assert!(sample == F::sample(&mut second), "sampling is not deterministic");
+        assert!(F::sample(&mut first) != sample, "consecutive samples repeat");
+    }
+
+    pub fn check_batch_inverse<F: Field>() {
+
+        assert!(F::batch_inverse(&[]).is_empty(), "batch inverse of empty slice is not empty");
+
+        let mut values: Vec<F> = (0..64).map(|_| random_element()).collect();
+        values[0] = F::zero();
+        values[17] = F::zero();
+        values[63] = F::zero();
+
+        let inverses = F::batch_inverse(&values);
+        assert!(inverses.len() == values.len(), "batch inverse changes length");
+        for (v, inv) in values.iter().zip(inverses.iter()) {
+            if v.is_zero() {
+                assert!(inv.is_zero(), "zero is not mapped to zero");
+            } else {
+                assert!(*inv == v.inverse(), "batch inverse disagrees with inverse");
+            }
+        }
+
+        assert!(F::batch_inverse(&[F::zero(), F::zero()]) == vec![F::zero(), F::zero()], "all-zero batch inverse fails");
+    }
+
+    #[test]
+    fn test_batch_inverse() {
+        check_batch_inverse::<FieldElement>();
+
+        let values: Vec<FieldElement> = (1..10).map(FieldElement::new).collect();
+        let inverses = FieldElement::batch_inverse(&values);
+        for i in 0..values.len() {
+            assert!(values[i] * inverses[i] == FieldElement::one(), "batch inverse fails");
+        }
+    }
+
+    pub fn check_encoding<F: Field>() {
+
+        for _ in 0..100 {
+            let a = random_element::<F>();
+            assert!(F::from_bytes(&a.to_bytes()) == Some(a), "encoding does not round trip");
+            assert!(bincode::serialize(&a).unwrap() == a.to_bytes(), "hashed and serialized bytes differ");
+            assert!(bincode::deserialize::<F>(&bincode::serialize(&a).unwrap()).unwrap() == a, "serialization does not round trip");
+        }
+
+        let bytes = F::one().to_bytes();
+        assert!(F::from_bytes(&bytes[1..]).is_none(), "short encoding decodes");
+        assert!(F::from_bytes(&[bytes.clone(), vec![0]].concat()).is_none(), "long encoding decodes");
+    }
+
+    #[test]
+    fn test_encoding() {
+        check_encoding::<FieldElement>();
+
+        let a = random_element::<FieldElement>();
+        assert!(FieldElement::from_be_bytes(&a.to_be_bytes()) == Some(a));
+        assert!(FieldElement::from_le_bytes(&a.to_le_bytes()) == Some(a));
+        assert!(a.to_le_bytes() == a.value.to_le_bytes(), "encoding is not little-endian");
+        assert!(a.to_be_bytes().iter().rev().eq(a.to_le_bytes().iter()), "encodings are not byte-reversed");
+
+        // values at or above the modulus are not canonical
+        for value in [P, P + 1, u128::MAX] {
+            assert!(FieldElement::from_be_bytes(&value.to_be_bytes()).is_none(), "unreduced value decodes");
+            assert!(FieldElement::from_le_bytes(&value.to_le_bytes()).is_none(), "unreduced value decodes");
+            assert!(bincode::deserialize::<FieldElement>(&value.to_le_bytes()).is_err(), "unreduced value deserializes");
+        }
+        assert!(FieldElement::from_be_bytes(&(P - 1).to_be_bytes()) == Some(-FieldElement::one()));
+        assert!(FieldElement::from_le_bytes(&(P - 1).to_le_bytes()) == Some(-FieldElement::one()));
+    }
+
+    #[test]
+    fn test_field_properties() {
+        check_field_properties::<FieldElement>();
+    }
+
+    #[test]
+    fn test_mul() {
+
+        let edge = [0, 1, 2, P - 1, P - 2, 1 << 119, (1 << 127) - 1, 1 << 127];
+
+        for a in edge.iter() {
+            for b in edge.iter() {
+                assert!(mul_mod(*a, *b) == mul_double_and_add(*a, *b), "fast multiplication disagrees on edge case");
+            }
+        }
+
+        for _ in 0..1000 {
+            let a = random_element::<FieldElement>();
+            let b = random_element::<FieldElement>();
+            assert!((a * b).value == mul_double_and_add(a.value, b.value), "fast multiplication disagrees with reference");
+            assert!((a * b).value < P, "fast multiplication is not reduced");
+        }
+
+        let a = random_element::<FieldElement>();
+        assert!(a * a.inverse() == FieldElement::one() || a.is_zero(), "multiplication by inverse is not one");
+    }
+
+    // timing of mul_mod against the double-and-add multiplication it replaced:
+    // cargo test --release bench_mul -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_mul() {
+
+        let n = 1_000_000;
+        let elements: Vec<u128> = (0..1024).map(|_| random_element::<FieldElement>().value).collect();
+
+        let start = Instant::now();
+        let mut acc = 1;
+        for i in 0..n {
+            acc = mul_mod(acc, elements[i % elements.len()]);
+        }
+        let fast = start.elapsed();
+
+        let start = Instant::now();
+        let mut reference = 1;
+        for i in 0..n {
+            reference = mul_double_and_add(reference, elements[i % elements.len()]);
+        }
+        let slow = start.elapsed();
+
+        assert!(acc == reference, "multiplications disagree");
+        println!("{n} multiplications: mul_mod {:?}, double-and-add {:?}", fast, slow);
+    }
+}
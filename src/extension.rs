@@ -0,0 +1,423 @@
+use std::ops;
+use serde_derive::{Deserialize, Serialize};
+use crate::algebra::{Field, FieldElement, PrimeField};
+use crate::hasher::Sponge;
+
+// prime fields that admit the quadratic extension F[x] / (x^2 - W)
+pub trait QuadraticExtensible: PrimeField {
+
+    // quadratic non-residue W
+    const QUADRATIC_NON_RESIDUE: u128;
+
+    // c such that (c * x)^2 = W * c^2 is the base field generator
+    const QUADRATIC_GENERATOR: u128;
+}
+
+// prime fields with p = 1 mod 3 that admit the cubic extension F[x] / (x^3 - W)
+pub trait CubicExtensible: PrimeField {
+
+    // cubic non-residue W
+    const CUBIC_NON_RESIDUE: u128;
+}
+
+// elements c0 + c1 * x of F[x] / (x^2 - W)
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+#[serde(bound = "F: Field")]
+pub struct QuadraticExtension<F: QuadraticExtensible> {
+    pub c0: F,
+    pub c1: F,
+}
+
+impl<F: QuadraticExtensible> QuadraticExtension<F> {
+
+    pub fn new(c0: F, c1: F) -> Self {
+        QuadraticExtension{c0, c1}
+    }
+
+    fn non_residue() -> F {
+        F::new(F::QUADRATIC_NON_RESIDUE)
+    }
+
+    // c0^2 - W c1^2, which lies in the base field
+    pub fn norm(&self) -> F {
+        self.c0 * self.c0 - QuadraticExtension::<F>::non_residue() * self.c1 * self.c1
+    }
+
+    // the p-power map, x^p = W^((p-1)/2) x = -x
+    pub fn frobenius(&self) -> Self {
+        QuadraticExtension::new(self.c0, -self.c1)
+    }
+}
+
+impl<F: QuadraticExtensible> From<F> for QuadraticExtension<F> {
+
+    fn from(c0: F) -> Self {
+        QuadraticExtension::new(c0, F::zero())
+    }
+}
+
+impl<F: QuadraticExtensible> Field for QuadraticExtension<F> {
+
+    const BITS: u32 = 2 * F::BITS;
+
+    // p^2 - 1 = (p - 1)(p + 1) and p + 1 contributes one more factor of two when p = 1 mod 4
+    const TWO_ADICITY: u32 = F::TWO_ADICITY + 1;
+
+    // high nibble 2 marks a quadratic extension of the base field
+    const ID: u8 = 0x20 | F::ID;
+
+    fn new(n: u128) -> Self {
+        QuadraticExtension::from(F::new(n))
+    }
+
+    fn zero() -> Self {
+        QuadraticExtension::from(F::zero())
+    }
+
+    fn one() -> Self {
+        QuadraticExtension::from(F::one())
+    }
+
+    // square root of the base field generator, so smaller roots of unity stay in the base field
+    fn generator() -> Self {
+        QuadraticExtension::new(F::zero(), F::new(F::QUADRATIC_GENERATOR))
+    }
+
+    fn inverse(self) -> Self {
+        let norm_inv = self.norm().inverse();
+        QuadraticExtension::new(self.c0 * norm_inv, -self.c1 * norm_inv)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero()
+    }
+
+    fn sample<S: Sponge>(sponge: &mut S) -> Self {
+        let c0 = F::sample(sponge);
+        QuadraticExtension::new(c0, F::sample(sponge))
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        [self.c0.to_bytes(), self.c1.to_bytes()].concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if !bytes.len().is_multiple_of(2) {
+            return None;
+        }
+        let (c0, c1) = bytes.split_at(bytes.len() / 2);
+        Some(QuadraticExtension::new(F::from_bytes(c0)?, F::from_bytes(c1)?))
+    }
+
+    fn prime_coordinates(self) -> Option<Vec<FieldElement>> {
+        Some([self.c0.prime_coordinates()?, self.c1.prime_coordinates()?].concat())
+    }
+}
+
+impl<F: QuadraticExtensible> PartialEq for QuadraticExtension<F> {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.c0 == other.c0 && self.c1 == other.c1
+    }
+}
+
+impl<F: QuadraticExtensible> ops::Add for QuadraticExtension<F> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        QuadraticExtension::new(self.c0 + rhs.c0, self.c1 + rhs.c1)
+    }
+}
+
+impl<F: QuadraticExtensible> ops::Sub for QuadraticExtension<F> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        QuadraticExtension::new(self.c0 - rhs.c0, self.c1 - rhs.c1)
+    }
+}
+
+impl<F: QuadraticExtensible> ops::Neg for QuadraticExtension<F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        QuadraticExtension::new(-self.c0, -self.c1)
+    }
+}
+
+impl<F: QuadraticExtensible> ops::Mul for QuadraticExtension<F> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let c0 = self.c0 * rhs.c0 + QuadraticExtension::<F>::non_residue() * self.c1 * rhs.c1;
+        let c1 = self.c0 * rhs.c1 + self.c1 * rhs.c0;
+        QuadraticExtension::new(c0, c1)
+    }
+}
+
+impl<F: QuadraticExtensible> ops::Div for QuadraticExtension<F> {
+    type Output = Self;
+
+    // division is multiplication by the inverse
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Self) -> Self {
+        self * rhs.inverse()
+    }
+}
+
+impl<F: QuadraticExtensible> ops::BitXor<u128> for QuadraticExtension<F> {
+    type Output = Self;
+
+    fn bitxor(self, exponent: u128) -> Self {
+        let mut acc = Self::one();
+        let mut base = self;
+        let mut e = exponent;
+
+        while e != 0 {
+            if e & 1 == 1 {
+                acc = acc * base;
+            }
+            base = base * base;
+            e >>= 1;
+        }
+        acc
+    }
+}
+
+
+// elements c0 + c1 * x + c2 * x^2 of F[x] / (x^3 - W)
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+#[serde(bound = "F: Field")]
+pub struct CubicExtension<F: CubicExtensible> {
+    pub c0: F,
+    pub c1: F,
+    pub c2: F,
+}
+
+impl<F: CubicExtensible> CubicExtension<F> {
+
+    pub fn new(c0: F, c1: F, c2: F) -> Self {
+        CubicExtension{c0, c1, c2}
+    }
+
+    fn non_residue() -> F {
+        F::new(F::CUBIC_NON_RESIDUE)
+    }
+
+    // the p-power map, x^p = W^((p-1)/3) x
+    pub fn frobenius(&self) -> Self {
+        let gamma = CubicExtension::<F>::non_residue() ^ ((F::MODULUS - 1) / 3);
+        CubicExtension::new(self.c0, self.c1 * gamma, self.c2 * gamma * gamma)
+    }
+
+    // product of all conjugates, which lies in the base field
+    pub fn norm(&self) -> F {
+        let conjugates = self.frobenius() * self.frobenius().frobenius();
+        (*self * conjugates).c0
+    }
+}
+
+impl<F: CubicExtensible> From<F> for CubicExtension<F> {
+
+    fn from(c0: F) -> Self {
+        CubicExtension::new(c0, F::zero(), F::zero())
+    }
+}
+
+impl<F: CubicExtensible> Field for CubicExtension<F> {
+
+    const BITS: u32 = 3 * F::BITS;
+
+    // p^3 - 1 = (p - 1)(p^2 + p + 1) and p^2 + p + 1 is odd
+    const TWO_ADICITY: u32 = F::TWO_ADICITY;
+
+    // high nibble 3 marks a cubic extension of the base field
+    const ID: u8 = 0x30 | F::ID;
+
+    fn new(n: u128) -> Self {
+        CubicExtension::from(F::new(n))
+    }
+
+    fn zero() -> Self {
+        CubicExtension::from(F::zero())
+    }
+
+    fn one() -> Self {
+        CubicExtension::from(F::one())
+    }
+
+    fn generator() -> Self {
+        CubicExtension::from(F::generator())
+    }
+
+    // a^-1 = (a^p * a^(p^2)) / norm(a)
+    fn inverse(self) -> Self {
+        let conjugates = self.frobenius() * self.frobenius().frobenius();
+        let norm_inv = (self * conjugates).c0.inverse();
+        CubicExtension::new(conjugates.c0 * norm_inv, conjugates.c1 * norm_inv, conjugates.c2 * norm_inv)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero() && self.c2.is_zero()
+    }
+
+    fn sample<S: Sponge>(sponge: &mut S) -> Self {
+        let c0 = F::sample(sponge);
+        let c1 = F::sample(sponge);
+        CubicExtension::new(c0, c1, F::sample(sponge))
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        [self.c0.to_bytes(), self.c1.to_bytes(), self.c2.to_bytes()].concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if !bytes.len().is_multiple_of(3) {
+            return None;
+        }
+        let width = bytes.len() / 3;
+        Some(CubicExtension::new(F::from_bytes(&bytes[..width])?, F::from_bytes(&bytes[width..2 * width])?, F::from_bytes(&bytes[2 * width..])?))
+    }
+
+    fn prime_coordinates(self) -> Option<Vec<FieldElement>> {
+        Some([self.c0.prime_coordinates()?, self.c1.prime_coordinates()?, self.c2.prime_coordinates()?].concat())
+    }
+}
+
+impl<F: CubicExtensible> PartialEq for CubicExtension<F> {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.c0 == other.c0 && self.c1 == other.c1 && self.c2 == other.c2
+    }
+}
+
+impl<F: CubicExtensible> ops::Add for CubicExtension<F> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        CubicExtension::new(self.c0 + rhs.c0, self.c1 + rhs.c1, self.c2 + rhs.c2)
+    }
+}
+
+impl<F: CubicExtensible> ops::Sub for CubicExtension<F> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        CubicExtension::new(self.c0 - rhs.c0, self.c1 - rhs.c1, self.c2 - rhs.c2)
+    }
+}
+
+impl<F: CubicExtensible> ops::Neg for CubicExtension<F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        CubicExtension::new(-self.c0, -self.c1, -self.c2)
+    }
+}
+
+impl<F: CubicExtensible> ops::Mul for CubicExtension<F> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let w = CubicExtension::<F>::non_residue();
+
+        let c0 = self.c0 * rhs.c0 + w * (self.c1 * rhs.c2 + self.c2 * rhs.c1);
+        let c1 = self.c0 * rhs.c1 + self.c1 * rhs.c0 + w * self.c2 * rhs.c2;
+        let c2 = self.c0 * rhs.c2 + self.c1 * rhs.c1 + self.c2 * rhs.c0;
+        CubicExtension::new(c0, c1, c2)
+    }
+}
+
+impl<F: CubicExtensible> ops::Div for CubicExtension<F> {
+    type Output = Self;
+
+    // division is multiplication by the inverse
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Self) -> Self {
+        self * rhs.inverse()
+    }
+}
+
+impl<F: CubicExtensible> ops::BitXor<u128> for CubicExtension<F> {
+    type Output = Self;
+
+    fn bitxor(self, exponent: u128) -> Self {
+        let mut acc = Self::one();
+        let mut base = self;
+        let mut e = exponent;
+
+        while e != 0 {
+            if e & 1 == 1 {
+                acc = acc * base;
+            }
+            base = base * base;
+            e >>= 1;
+        }
+        acc
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::FieldElement;
+    use crate::algebra::random_element;
+    use crate::algebra::tests::check_field_properties;
+    use crate::goldilocks::Goldilocks;
+    use crate::hasher::KeccakSponge;
+
+    #[test]
+    fn test_extension_properties() {
+        check_field_properties::<QuadraticExtension<FieldElement>>();
+        check_field_properties::<QuadraticExtension<Goldilocks>>();
+        check_field_properties::<CubicExtension<Goldilocks>>();
+    }
+
+    #[test]
+    fn test_frobenius() {
+
+        for _ in 0..20 {
+            let a = random_element::<QuadraticExtension<FieldElement>>();
+            let b = random_element::<QuadraticExtension<FieldElement>>();
+
+            // frobenius is the p-power map, a field automorphism of order 2
+            assert!(a.frobenius() == a ^ FieldElement::MODULUS, "quadratic frobenius is not the p-power map");
+            assert!(a.frobenius().frobenius() == a, "quadratic frobenius does not have order 2");
+            assert!((a * b).frobenius() == a.frobenius() * b.frobenius(), "quadratic frobenius is not multiplicative");
+            assert!(QuadraticExtension::from(a.norm()) == a * a.frobenius(), "quadratic norm fails");
+
+            let c = random_element::<CubicExtension<Goldilocks>>();
+            let d = random_element::<CubicExtension<Goldilocks>>();
+
+            assert!(c.frobenius() == c ^ Goldilocks::MODULUS, "cubic frobenius is not the p-power map");
+            assert!(c.frobenius().frobenius().frobenius() == c, "cubic frobenius does not have order 3");
+            assert!((c + d).frobenius() == c.frobenius() + d.frobenius(), "cubic frobenius is not additive");
+            assert!(CubicExtension::from(c.norm()) == c * c.frobenius() * c.frobenius().frobenius(), "cubic norm fails");
+        }
+
+        // base field elements are fixed points
+        let x = random_element::<Goldilocks>();
+        assert!(CubicExtension::from(x).frobenius() == CubicExtension::from(x));
+    }
+
+    #[test]
+    fn test_sample() {
+
+        let mut sponge = KeccakSponge::new();
+        sponge.absorb(b"transcript");
+        let a = QuadraticExtension::<FieldElement>::sample(&mut sponge.clone());
+
+        assert!(a == QuadraticExtension::<FieldElement>::sample(&mut sponge.clone()), "sampling is not deterministic");
+        assert!(!a.c1.is_zero(), "sampled element lies in the base field");
+
+        // every coefficient comes from its own squeeze of the transcript sponge
+        let mut expected = sponge.clone();
+        let c0 = FieldElement::sample(&expected.squeeze());
+        assert!(a == QuadraticExtension::new(c0, FieldElement::sample(&expected.squeeze())), "coefficients are not squeezed in order");
+
+        let mut other = KeccakSponge::new();
+        other.absorb(b"other transcript");
+        assert!(a != QuadraticExtension::<FieldElement>::sample(&mut other));
+    }
+}
@@ -1,24 +1,30 @@
-use crate::algebra::FieldElement;
-use crate::ip::{Object, ProofStream};
-use crate::merkle::Merkle;
-use crate::keccak::keccak256;
-
+use std::marker::PhantomData;
+use crate::algebra::{Field, FieldElement};
+use crate::ip::{Object, ProofError, ProofStream, VerifyError, FORMAT_VERSION};
+use crate::hasher::{Digest, Hasher, Keccak};
+use crate::merkle::{self, MerkleTree};
+use crate::univariate::Polynomial;
+
+// the evaluation domain lives in F, every codeword (including the first) and the challenges in E,
+// H commits to codewords and derives the challenges
 #[derive(Debug)]
-pub struct Fri {
-    offset: FieldElement,
-    omega: FieldElement, 
+pub struct Fri<F: Field = FieldElement, E: Field + From<F> = F, H: Hasher = Keccak> {
+    offset: F,
+    omega: F, 
     domain_length : u128,
     expansion_factor: u128,
-    num_colinearity_tests: u128
+    num_colinearity_tests: u128,
+    challenge_field: PhantomData<E>,
+    hasher: PhantomData<H>
 }
 
-impl Fri {
+impl<F: Field, E: Field + From<F>, H: Hasher> Fri<F, E, H> {
     
-    pub fn new(offset: FieldElement, omega: FieldElement, initial_domain_length: u128, 
+    pub fn new(offset: F, omega: F, initial_domain_length: u128, 
             expansion_factor: u128,num_colinearity_test: u128 ) -> Self {
         
-        return Fri{offset: offset, omega: omega, domain_length: initial_domain_length,
-        expansion_factor: expansion_factor, num_colinearity_tests: num_colinearity_test};
+        Fri{offset, omega, domain_length: initial_domain_length,
+        expansion_factor, num_colinearity_tests: num_colinearity_test, challenge_field: PhantomData, hasher: PhantomData}
     }
     
     pub fn num_rounds(&self) -> u128 {
@@ -36,7 +42,7 @@ impl Fri {
     pub fn sample_index(byte_array: &[u8], size: usize) -> usize {
         let mut acc = FieldElement::new(0);
         for b in byte_array.iter() {
-            acc = acc.clone() * FieldElement::new(2_u128.pow(8))  ^ (*b as u128);
+            acc = acc * FieldElement::new(2_u128.pow(8)) + FieldElement::new(*b as u128);
         }
 
         (acc.value % size as u128) as usize
@@ -55,9 +61,9 @@ impl Fri {
         let mut counter: i32 = 0;
 
         while indices.len() < number {
-            let bytes: Vec<u8> = [seed.clone(), &counter.to_be_bytes()].concat();
-            let index = Fri::sample_index(&keccak256(&bytes[..]), size);
-            let mut reduced_index = index % reduced_size;
+            let bytes: Vec<u8> = [seed, &counter.to_be_bytes()].concat();
+            let index = Self::sample_index(&H::hash(&bytes[..]).to_bytes(), size as usize);
+            let reduced_index = index % reduced_size;
             counter += 1;
 
             if !reduced_indices.contains(&reduced_index) {
@@ -71,8 +77,8 @@ impl Fri {
 
     }
 
-    pub fn eval_domain(&self) -> Vec<FieldElement> {
-        let mut domain: Vec<FieldElement> = vec![];
+    pub fn eval_domain(&self) -> Vec<F> {
+        let mut domain: Vec<F> = vec![];
         
         for i in 0..self.domain_length {
             
@@ -81,40 +87,54 @@ impl Fri {
         domain
     }
     
-    pub fn commit(&self, codeword: &mut Vec<FieldElement>, proof_stream: &mut ProofStream) -> Vec<Vec<FieldElement>> {
+    // a codeword over F is lifted with E::from by the caller before it is committed
+    pub fn commit(&self, codeword: &mut Vec<E>, proof_stream: &mut ProofStream<E, H>) -> (Vec<Vec<E>>, Vec<MerkleTree<H>>) {
         
-        let one = FieldElement::one();
-        let two = FieldElement::new(2);
-        let mut omega = self.omega.clone();
-        let mut offset = self.offset.clone();
-        let mut codewords :Vec<Vec<FieldElement>> = vec![];
+        let one = E::one();
+        let two = E::new(2);
+        let mut omega = self.omega;
+        let mut offset = self.offset;
+        let mut codewords :Vec<Vec<E>> = vec![];
+        let mut trees: Vec<MerkleTree<H>> = vec![];
         
         for r in 0..self.num_rounds(){
 
-            let N = codeword.len() as u128;
+            let n = codeword.len() as u128;
 
             //make sure omega has the right order
-            assert!(omega^(N-1) == omega.inverse(),"error in commit: omega does not have the right order!" );
+            assert!(omega^(n-1) == omega.inverse(),"error in commit: omega does not have the right order!" );
 
             // compute and send Merkle root
-            let root = Merkle::commit(codeword);
-            proof_stream.push(&Object::MerkleRoot(root));
+            let tree = MerkleTree::build(codeword);
+            proof_stream.push(&Object::MerkleRoot(tree.root()));
+            trees.push(tree);
 
             if r == self.num_rounds() -1 {
                 break;
             }
 
-            let mut alpha = FieldElement::sample(&proof_stream.prover_fiat_shamir());
+            let alpha: E = proof_stream.prover_sample();
             
             codewords.push(codeword.clone());
 
             
-            let mut temp_codeword:Vec<FieldElement> = vec![];
+            let mut temp_codeword:Vec<E> = vec![];
 
             
-            for i in (0..N/2) {
+            // invert the first half of the domain in one batch
+            let mut xs: Vec<F> = vec![];
+            let mut x = offset;
+            for _ in 0..n/2 {
+                xs.push(x);
+                x = x * omega;
+            }
+            let x_inverses = F::batch_inverse(&xs);
+            let two_inverse = two.inverse();
+
+            for i in 0..n/2 {
 
-                let word = two.inverse() * ( ( one.clone() + alpha.clone() / (offset.clone() * (omega.clone()^i as u128)) ) * codeword[i as usize] + (one.clone() - alpha.clone() / (offset.clone() * (omega.clone()^i as u128)) ) * codeword[ (N/2 + i) as usize]);
+                let alpha_over_x = alpha * E::from(x_inverses[i as usize]);
+                let word = two_inverse * ( ( one + alpha_over_x ) * codeword[i as usize] + (one - alpha_over_x ) * codeword[ (n/2 + i) as usize]);
                 temp_codeword.push(word);
             }
 
@@ -129,41 +149,37 @@ impl Fri {
         
         codewords.push(codeword.clone());
 
-        codewords
+        (codewords, trees)
     
     }
 
-    fn query (&self, current_codeword: &Vec<FieldElement>, next_codeword: &Vec<FieldElement>, c_indices: Vec<usize>,  proof_stream: &mut ProofStream) -> Vec<usize>{
+    fn query (&self, current_codeword: &[E], next_codeword: &[E], current_tree: &MerkleTree<H>, next_tree: &MerkleTree<H>, c_indices: Vec<usize>,  proof_stream: &mut ProofStream<E, H>) -> Vec<usize>{
 
-        let mut a_indices = c_indices.clone();
-        let mut b_indices:Vec<usize> = c_indices.iter().map(|x| x + (current_codeword.len()/2) ).collect();
+        let a_indices = c_indices.clone();
+        let b_indices:Vec<usize> = c_indices.iter().map(|x| x + (current_codeword.len()/2) ).collect();
 
         // reveal leafs
-        for s in (0..self.num_colinearity_tests as usize) {
+        for s in 0..self.num_colinearity_tests as usize {
 
             proof_stream.push(&Object::ColinearityTest((current_codeword[a_indices[s]], current_codeword[b_indices[s]], next_codeword[c_indices[s]])));
 
         }
 
-        // reveal authentication paths
-        for s in (0..self.num_colinearity_tests as usize) {
-
-            proof_stream.push( &Object::MerklePath(Merkle::open(a_indices[s], current_codeword)));
-            proof_stream.push( &Object::MerklePath(Merkle::open(b_indices[s], current_codeword)));
-            proof_stream.push( &Object::MerklePath(Merkle::open(c_indices[s], next_codeword)));
-
-        }
+        // reveal authentication paths, one multi-proof per tree
+        let ab_indices = [&a_indices[..], &b_indices[..]].concat();
+        proof_stream.push( &Object::MerkleMultiPath(current_tree.open_many(&ab_indices)));
+        proof_stream.push( &Object::MerkleMultiPath(next_tree.open_many(&c_indices)));
         
-        vec![a_indices, b_indices].concat()
+        [a_indices, b_indices].concat()
     }
 
-    fn prove (&self, codeword: &mut Vec<FieldElement>, proof_stream: &mut ProofStream)  -> Vec<usize>{
+    pub fn prove (&self, codeword: &mut Vec<E>, proof_stream: &mut ProofStream<E, H>)  -> Vec<usize>{
 
         assert!(self.domain_length == codeword.len() as u128);
 
         // commit phase
 
-        let mut codewords = self.commit(codeword, proof_stream);
+        let (codewords, trees) = self.commit(codeword, proof_stream);
 
         // get indices
         let mut top_level_indices = self.sample_indeices(&proof_stream.prover_fiat_shamir()[..], codewords[1].len() as u128, codewords[codewords.len()-1].len(), self.num_colinearity_tests as usize);
@@ -173,44 +189,298 @@ impl Fri {
         // query phase 
         for i in 0..codewords.len() -1{
             indices = indices.iter().map(|x| x % (codewords[i].len()/2)).collect();
-            self.query(&codewords[i], &codewords[i+1], indices, proof_stream);
+            let opened = self.query(&codewords[i], &codewords[i+1], &trees[i], &trees[i+1], indices.clone(), proof_stream);
+
+            // the verifier learns the top-level values at both a and b indices
+            if i == 0 {
+                top_level_indices = opened;
+            }
         }   
 
         top_level_indices
 
     }
 
-    fn verify (&self, proof_stream: &mut ProofStream) -> Vec<(usize, FieldElement)> {
+    pub fn verify (&self, proof_stream: &mut ProofStream<E, H>) -> Result<Vec<(usize, E)>, VerifyError> {
 
+        // older proofs have other objects, they can be read but not verified
+        if proof_stream.version() != FORMAT_VERSION {
+            return Err(VerifyError::Proof(ProofError::UnsupportedVersion));
+        }
 
-        let mut omega = self.omega.clone();
-        let mut offset = self.offset.clone();
+        let mut omega = self.omega;
+        let mut offset = self.offset;
 
-        let mut roots: Vec<Object> = vec![];
-        let mut alphas: Vec<FieldElement> = vec![];
+        let mut roots: Vec<H::Digest> = vec![];
+        let mut alphas: Vec<E> = vec![];
 
+        // extract all roots and alphas
         for r in 0..self.num_rounds() as usize {
-            roots.push(proof_stream.pull());
-            alphas.push(FieldElement::sample(&proof_stream.verifier_fiat_shamir()));
+            roots.push(proof_stream.pull_root()?);
+
+            // the prover draws no challenge after the last root
+            if r == self.num_rounds() as usize - 1 {
+                break;
+            }
+            alphas.push(proof_stream.verifier_sample());
         }
 
-        let last_codeword = proof_stream.pull();
+        // extract last codeword and check it matches the last root
+        let last_codeword = proof_stream.pull_codeword()?;
 
-        assert!(roots[roots.len() - 1 ] != Merkle::commit(last_codeword));
-            
+        if last_codeword.len() as u128 != self.domain_length >> (self.num_rounds() - 1) {
+            return Err(VerifyError::LastCodeword);
+        }
+
+        if roots[roots.len() - 1] != MerkleTree::<H>::build(&last_codeword).root() {
+            return Err(VerifyError::LastCodeword);
+        }
+
+        // check that the last codeword is of low degree
+        let degree = (last_codeword.len() as i128 / self.expansion_factor as i128) - 1;
+        let mut last_omega = omega;
+        let mut last_offset = offset;
+        for _ in 0..self.num_rounds() - 1 {
+            last_omega = last_omega^2;
+            last_offset = last_offset^2;
+        }
 
+        assert!(last_omega.inverse() == last_omega^(last_codeword.len() as u128 - 1), "error in verify: omega does not have the right order!");
 
+        let mut last_domain: Vec<E> = vec![];
+        for i in 0..last_codeword.len() {
+            last_domain.push(E::from(last_offset * (last_omega^i as u128)));
+        }
+
+        let poly = Polynomial::interpolate_domain(&last_domain, &last_codeword);
+
+        if poly.degree() > degree {
+            return Err(VerifyError::LowDegree);
+        }
+
+        // get indices
+        let top_level_indices = self.sample_indeices(&proof_stream.verifier_fiat_shamir()[..], self.domain_length >> 1, (self.domain_length >> (self.num_rounds() - 1)) as usize, self.num_colinearity_tests as usize);
 
+        let mut polynomial_values: Vec<(usize, E)> = vec![];
 
+        // for every round, check consistency of subsequent layers
+        for r in 0..self.num_rounds() as usize - 1 {
 
+            let c_indices: Vec<usize> = top_level_indices.iter().map(|x| x % (self.domain_length >> (r + 1)) as usize).collect();
+            let a_indices = c_indices.clone();
+            let b_indices: Vec<usize> = a_indices.iter().map(|x| x + (self.domain_length >> (r + 1)) as usize).collect();
 
+            let mut aa: Vec<E> = vec![];
+            let mut bb: Vec<E> = vec![];
+            let mut cc: Vec<E> = vec![];
 
-        let r: Vec<(usize, FieldElement)> = vec![];
-        r
+            // read values and check colinearity
+            for s in 0..self.num_colinearity_tests as usize {
+                let (ay, by, cy) = proof_stream.pull_colinearity_test()?;
+                aa.push(ay);
+                bb.push(by);
+                cc.push(cy);
+
+                // record top-layer values for the caller
+                if r == 0 {
+                    polynomial_values.push((a_indices[s], ay));
+                    polynomial_values.push((b_indices[s], by));
+                }
+
+                let ax = E::from(offset * (omega^a_indices[s] as u128));
+                let bx = E::from(offset * (omega^b_indices[s] as u128));
+                let cx = alphas[r];
+
+                if !Polynomial::test_colinearity(vec![ax, bx, cx], vec![ay, by, cy]) {
+                    return Err(VerifyError::Colinearity);
+                }
+            }
+
+            // verify authentication paths
+            let current_length = (self.domain_length >> r) as usize;
+            let ab_indices = [&a_indices[..], &b_indices[..]].concat();
+            let ab_leafs = [&aa[..], &bb[..]].concat();
+            let proof = proof_stream.pull_multi_path()?;
+            if !merkle::verify_many::<H, E>(roots[r], current_length, &ab_indices, &ab_leafs, &proof) {
+                return Err(VerifyError::FriPath);
+            }
+
+            let proof = proof_stream.pull_multi_path()?;
+            if !merkle::verify_many::<H, E>(roots[r + 1], current_length / 2, &c_indices, &cc, &proof) {
+                return Err(VerifyError::FriPath);
+            }
+
+            // square omega and offset to prepare for next round
+            omega = omega^2;
+            offset = offset^2;
+        }
+
+        Ok(polynomial_values)
     }
 
+}
 
-    
 
- 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakSponge;
+    use crate::ip::ProofError;
+    use crate::keccak::keccak256;
+
+    // keccak with a prefix byte, stands in for a second hash function
+    #[derive(Debug, Clone, PartialEq)]
+    struct PrefixedKeccak;
+
+    impl Hasher for PrefixedKeccak {
+
+        type Digest = [u8; 32];
+
+        type Sponge = KeccakSponge;
+
+        const ID: u8 = 0xf0;
+
+        fn hash(bytes: &[u8]) -> [u8; 32] {
+            keccak256(&[&[0xf0][..], bytes].concat()[..])
+        }
+
+        fn two_to_one(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+            Self::hash(&[*left, *right].concat()[..])
+        }
+    }
+
+    fn setup(degree: usize, expansion_factor: usize, num_colinearity_tests: usize) -> (Fri, Polynomial) {
+
+        let initial_codeword_length = (degree + 1) * expansion_factor;
+
+        let omega = FieldElement::generator().primitive_nth_root(initial_codeword_length as u128);
+        let generator = FieldElement::generator();
+
+        assert!(omega^(initial_codeword_length as u128) == FieldElement::one(), "omega not nth root of unity");
+        assert!(omega^(initial_codeword_length as u128 / 2) != FieldElement::one(), "omega not primitive");
+
+        let fri = Fri::new(generator, omega, initial_codeword_length as u128, expansion_factor as u128, num_colinearity_tests as u128);
+
+        let mut coefs: Vec<FieldElement> = vec![];
+        for i in 0..degree + 1 {
+            coefs.push(FieldElement::new(i as u128));
+        }
+
+        (fri, Polynomial::new(coefs))
+    }
+
+    #[test]
+    fn test_fri() {
+
+        let (fri, polynomial) = setup(63, 4, 17);
+        let domain = fri.eval_domain();
+        let mut codeword = polynomial.evaluate_domain(&domain);
+
+        // a valid codeword should be accepted
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        let points = fri.verify(&mut verifier_stream);
+        assert!(points.is_ok(), "proof should be valid, but is rejected");
+
+        for (x, y) in points.unwrap() {
+            assert!(polynomial.evaluate(domain[x]) == y, "polynomial evaluates to wrong value");
+        }
+
+        // disturb then test for failure
+        for i in (0..63 * 4).step_by(4) {
+            codeword[i] = FieldElement::zero();
+        }
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword, &mut proof_stream);
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_err(), "proof should fail, but is accepted");
+    }
+
+    #[test]
+    fn test_fri_transcript() {
+
+        let (fri, polynomial) = setup(255, 4, 8);
+        assert!(fri.num_rounds() > 3, "transcript should span several rounds");
+
+        let domain = fri.eval_domain();
+        let codeword = polynomial.evaluate_domain(&domain);
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+
+        // the verifier replays the prover's stream directly and after a round trip through bytes
+        assert!(fri.verify(&mut proof_stream).is_ok(), "proof is rejected on the prover's stream");
+
+        let proof = proof_stream.serial();
+        let mut verifier_stream = ProofStream::deserial(&proof).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_ok(), "proof is rejected after serialization");
+
+        // altering the first Merkle root changes every challenge
+        let root: [u8;32] = MerkleTree::<Keccak>::build(&codeword).root();
+        let position = proof.windows(32).position(|w| w == root).unwrap();
+        let mut tampered = proof.clone();
+        tampered[position] ^= 1;
+        let mut verifier_stream = ProofStream::deserial(&tampered).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_err(), "tampered transcript is accepted");
+
+        // older versions still read but do not verify
+        let mut previous = proof.clone();
+        previous[4] = FORMAT_VERSION - 1;
+        let mut verifier_stream = ProofStream::deserial(&previous).unwrap();
+        assert!(fri.verify(&mut verifier_stream) == Err(VerifyError::Proof(ProofError::UnsupportedVersion)), "older proof is verified");
+    }
+
+    #[test]
+    fn test_fri_large_transcript() {
+
+        let (fri, polynomial) = setup(4095, 4, 64);
+        let domain = fri.eval_domain();
+        let codeword = polynomial.evaluate_domain(&domain);
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+
+        // roots, last codeword, a colinearity test per query and two multi-proofs in every round but the last
+        let num_objects = fri.num_rounds() + 1 + (64 + 2) * (fri.num_rounds() - 1);
+        assert!(num_objects > 255, "transcript should exceed 255 objects");
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        let points = fri.verify(&mut verifier_stream).expect("large fri proof is rejected");
+
+        for (x, y) in points {
+            assert!(polynomial.evaluate(domain[x]) == y, "polynomial evaluates to wrong value");
+        }
+        assert!(verifier_stream.pull().is_err(), "verifier leaves objects unread");
+    }
+
+    #[test]
+    fn test_fri_hasher() {
+
+        let (_, polynomial) = setup(63, 4, 17);
+        let initial_codeword_length = 64 * 4;
+        let omega = FieldElement::generator().primitive_nth_root(initial_codeword_length);
+        let fri: Fri<FieldElement, FieldElement, PrefixedKeccak> = Fri::new(FieldElement::generator(), omega, initial_codeword_length, 4, 17);
+
+        let domain = fri.eval_domain();
+        let codeword = polynomial.evaluate_domain(&domain);
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+
+        let proof = proof_stream.serial();
+        let mut verifier_stream: ProofStream<FieldElement, PrefixedKeccak> = ProofStream::deserial(&proof).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_ok(), "proof with another hasher is rejected");
+
+        // the first root is the prefixed hash tree, not the keccak one
+        let root = MerkleTree::<PrefixedKeccak>::build(&codeword).root();
+        assert!(proof.windows(32).any(|w| w == root), "proof does not commit with the chosen hasher");
+        assert!(root != MerkleTree::<Keccak>::build(&codeword).root(), "hashers agree on the root");
+
+        // a reader expecting keccak refuses the proof
+        assert!(ProofStream::<FieldElement, Keccak>::deserial(&proof).err() == Some(ProofError::HashMismatch), "hash id is not checked");
+    }
 }
@@ -0,0 +1,412 @@
+use std::ops;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
+use crate::algebra::{Field, PrimeField};
+use crate::hasher::Sponge;
+use crate::extension::{CubicExtensible, CubicExtension, QuadraticExtensible, QuadraticExtension};
+
+// p = 2^64 - 2^32 + 1
+pub const GOLDILOCKS_P: u64 = 0xffff_ffff_0000_0001;
+
+// 2^64 - p = 2^32 - 1
+const EPSILON: u64 = 0xffff_ffff;
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct Goldilocks {
+    pub value: u64,
+}
+
+impl Goldilocks {
+
+    pub fn new(n: u128) -> Goldilocks {
+        Goldilocks{value: (n % GOLDILOCKS_P as u128) as u64}
+    }
+
+    pub fn zero() -> Goldilocks {
+        Goldilocks{value: 0}
+    }
+
+    pub fn one() -> Goldilocks {
+        Goldilocks{value: 1}
+    }
+
+    // generator of the subgroup of order 2^32, 7^((p-1)/2^32)
+    pub fn generator() -> Goldilocks {
+        Goldilocks{value: 1753635133440165772}
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    pub fn inverse(self) -> Goldilocks {
+        assert!(!self.is_zero(), "cannot invert zero");
+        self ^ (GOLDILOCKS_P - 2) as u128
+    }
+
+    pub fn sample(byte_array: &[u8;32]) -> Goldilocks {
+        let mut acc = Goldilocks::zero();
+
+        for b in byte_array.iter() {
+            acc = acc * Goldilocks::new(256) + Goldilocks::new(*b as u128);
+        }
+        acc
+    }
+}
+
+// reduces a 128-bit product modulo p using 2^64 = 2^32 - 1 and 2^96 = -1
+#[inline]
+fn reduce128(x: u128) -> u64 {
+    let x_lo = x as u64;
+    let x_hi = (x >> 64) as u64;
+    let x_hi_hi = x_hi >> 32;
+    let x_hi_lo = x_hi & EPSILON;
+
+    let (mut t0, borrow) = x_lo.overflowing_sub(x_hi_hi);
+    if borrow {
+        t0 = t0.wrapping_sub(EPSILON);
+    }
+    let t1 = x_hi_lo * EPSILON;
+
+    let (res, carry) = t0.overflowing_add(t1);
+    let res = res.wrapping_add(EPSILON * carry as u64);
+
+    if res >= GOLDILOCKS_P {
+        return res - GOLDILOCKS_P;
+    }
+    res
+}
+
+impl Field for Goldilocks {
+
+    const BITS: u32 = 64;
+
+    const TWO_ADICITY: u32 = 32;
+
+    const ID: u8 = 2;
+
+    fn new(n: u128) -> Self {
+        Goldilocks::new(n)
+    }
+
+    fn zero() -> Self {
+        Goldilocks::zero()
+    }
+
+    fn one() -> Self {
+        Goldilocks::one()
+    }
+
+    fn generator() -> Self {
+        Goldilocks::generator()
+    }
+
+    fn inverse(self) -> Self {
+        Goldilocks::inverse(self)
+    }
+
+    fn is_zero(&self) -> bool {
+        Goldilocks::is_zero(self)
+    }
+
+    fn sample<S: Sponge>(sponge: &mut S) -> Self {
+        Goldilocks::sample(&sponge.squeeze())
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        self.value.to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let value = u64::from_le_bytes(bytes.try_into().ok()?);
+        if value >= GOLDILOCKS_P {
+            return None;
+        }
+        Some(Goldilocks{value})
+    }
+}
+
+impl Serialize for Goldilocks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Goldilocks {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Goldilocks, D::Error> {
+        let value = u64::deserialize(deserializer)?;
+        if value >= GOLDILOCKS_P {
+            return Err(D::Error::custom("field element is not reduced"));
+        }
+        Ok(Goldilocks{value})
+    }
+}
+
+impl PartialEq for Goldilocks {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl ops::Add for Goldilocks {
+    type Output = Goldilocks;
+
+    fn add(self, rhs: Goldilocks) -> Goldilocks {
+        let (sum, overflow) = self.value.overflowing_add(rhs.value);
+
+        if overflow || sum >= GOLDILOCKS_P {
+            return Goldilocks{value: sum.wrapping_sub(GOLDILOCKS_P)};
+        }
+        Goldilocks{value: sum}
+    }
+}
+
+impl ops::Sub for Goldilocks {
+    type Output = Goldilocks;
+
+    fn sub(self, rhs: Goldilocks) -> Goldilocks {
+        let (diff, borrow) = self.value.overflowing_sub(rhs.value);
+
+        if borrow {
+            return Goldilocks{value: diff.wrapping_add(GOLDILOCKS_P)};
+        }
+        Goldilocks{value: diff}
+    }
+}
+
+impl ops::Neg for Goldilocks {
+    type Output = Goldilocks;
+
+    fn neg(self) -> Goldilocks {
+        Goldilocks::zero() - self
+    }
+}
+
+impl ops::Mul for Goldilocks {
+    type Output = Goldilocks;
+
+    fn mul(self, rhs: Goldilocks) -> Goldilocks {
+        Goldilocks{value: reduce128(self.value as u128 * rhs.value as u128)}
+    }
+}
+
+impl ops::Div for Goldilocks {
+    type Output = Goldilocks;
+
+    // division is multiplication by the inverse
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Goldilocks) -> Goldilocks {
+        self * rhs.inverse()
+    }
+}
+
+impl ops::BitXor<u128> for Goldilocks {
+    type Output = Goldilocks;
+
+    fn bitxor(self, exponent: u128) -> Goldilocks {
+        let mut acc = Goldilocks::one();
+        let mut base = self;
+        let mut e = exponent;
+
+        while e != 0 {
+            if e & 1 == 1 {
+                acc = acc * base;
+            }
+            base = base * base;
+            e >>= 1;
+        }
+        acc
+    }
+}
+
+
+impl PrimeField for Goldilocks {
+
+    const MODULUS: u128 = GOLDILOCKS_P as u128;
+}
+
+impl QuadraticExtensible for Goldilocks {
+
+    const QUADRATIC_NON_RESIDUE: u128 = 7;
+
+    const QUADRATIC_GENERATOR: u128 = 2787638404040055058;
+}
+
+impl CubicExtensible for Goldilocks {
+
+    const CUBIC_NON_RESIDUE: u128 = 2;
+}
+
+pub type GoldilocksExt2 = QuadraticExtension<Goldilocks>;
+
+pub type GoldilocksExt3 = CubicExtension<Goldilocks>;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::random_element;
+    use crate::algebra::tests::{check_batch_inverse, check_encoding, check_field_properties};
+    use crate::fri::Fri;
+    use crate::ip::ProofStream;
+    use crate::univariate::Polynomial;
+
+    #[test]
+    fn test_goldilocks_properties() {
+        check_field_properties::<Goldilocks>();
+        check_field_properties::<GoldilocksExt2>();
+        check_field_properties::<GoldilocksExt3>();
+        check_batch_inverse::<Goldilocks>();
+        check_batch_inverse::<GoldilocksExt2>();
+    }
+
+    #[test]
+    fn test_encoding() {
+        check_encoding::<Goldilocks>();
+        check_encoding::<GoldilocksExt2>();
+        check_encoding::<GoldilocksExt3>();
+
+        assert!(Goldilocks::from_bytes(&GOLDILOCKS_P.to_le_bytes()).is_none(), "unreduced value decodes");
+        assert!(bincode::deserialize::<Goldilocks>(&u64::MAX.to_le_bytes()).is_err(), "unreduced value deserializes");
+
+        // a reduced first coordinate does not save an unreduced second one
+        let bytes = [1u64.to_le_bytes(), GOLDILOCKS_P.to_le_bytes()].concat();
+        assert!(bincode::deserialize::<GoldilocksExt2>(&bytes).is_err(), "unreduced extension element deserializes");
+    }
+
+    #[test]
+    fn test_reduction() {
+
+        let edge: Vec<u64> = vec![0, 1, 2, EPSILON, EPSILON + 1, GOLDILOCKS_P - 1, GOLDILOCKS_P - 2, 1 << 63, u64::MAX % GOLDILOCKS_P];
+
+        for a in edge.iter() {
+            for b in edge.iter() {
+                let product = Goldilocks{value: *a} * Goldilocks{value: *b};
+                assert!(product.value as u128 == (*a as u128 * *b as u128) % GOLDILOCKS_P as u128, "goldilocks reduction fails");
+
+                let sum = Goldilocks{value: *a} + Goldilocks{value: *b};
+                assert!(sum.value as u128 == (*a as u128 + *b as u128) % GOLDILOCKS_P as u128, "goldilocks addition fails");
+            }
+        }
+
+        for _ in 0..1000 {
+            let a = random_element::<Goldilocks>();
+            let b = random_element::<Goldilocks>();
+            assert!((a * b).value as u128 == (a.value as u128 * b.value as u128) % GOLDILOCKS_P as u128, "goldilocks reduction fails");
+        }
+    }
+
+    #[test]
+    fn test_roots_of_unity() {
+
+        let root = Goldilocks::primitive_nth_root(1 << 32);
+        assert!(root ^ (1 << 32) == Goldilocks::one(), "generator is not a 2^32-th root of unity");
+        assert!(root ^ (1 << 31) == -Goldilocks::one(), "generator is not primitive");
+
+        let root = GoldilocksExt2::primitive_nth_root(1 << 33);
+        assert!(root ^ (1 << 33) == GoldilocksExt2::one(), "extension generator is not a 2^33-th root of unity");
+        assert!(root ^ (1 << 32) == -GoldilocksExt2::one(), "extension generator is not primitive");
+
+        // smaller roots of unity in the extension are base field elements
+        assert!(GoldilocksExt2::primitive_nth_root(1 << 32) == GoldilocksExt2::from(Goldilocks::generator()));
+    }
+
+    #[test]
+    fn test_polynomial() {
+
+        let n: usize = 256;
+        let omega = Goldilocks::primitive_nth_root(n as u128);
+        let domain: Vec<Goldilocks> = (0..n).map(|i| Goldilocks::generator() * (omega ^ i as u128)).collect();
+        let values: Vec<Goldilocks> = (0..n).map(|_| random_element()).collect();
+
+        let poly = Polynomial::interpolate_domain(&domain, &values);
+        assert!(poly.evaluate_domain(&domain) == values, "goldilocks interpolation fails");
+
+        for i in (0..n).step_by(17) {
+            assert!(poly.evaluate(domain[i]) == values[i], "goldilocks evaluation fails");
+        }
+    }
+
+    fn fri_round_trip<F: Field>() {
+
+        let degree = 63;
+        let expansion_factor = 4;
+        let initial_codeword_length = (degree + 1) * expansion_factor;
+
+        let omega = F::primitive_nth_root(initial_codeword_length as u128);
+        let fri: Fri<F> = Fri::new(F::generator(), omega, initial_codeword_length as u128, expansion_factor as u128, 17);
+
+        let polynomial = Polynomial::new((0..degree + 1).map(|_| random_element::<F>()).collect());
+        let domain = fri.eval_domain();
+        let mut codeword = polynomial.evaluate_domain(&domain);
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        let points = fri.verify(&mut verifier_stream).expect("valid fri proof is rejected");
+
+        for (x, y) in points {
+            assert!(polynomial.evaluate(domain[x]) == y, "polynomial evaluates to wrong value");
+        }
+
+        for i in (0..degree * expansion_factor).step_by(expansion_factor) {
+            codeword[i] = F::zero();
+        }
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword, &mut proof_stream);
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_err(), "invalid fri proof is accepted");
+    }
+
+    #[test]
+    fn test_fri() {
+        fri_round_trip::<Goldilocks>();
+        fri_round_trip::<GoldilocksExt2>();
+    }
+
+    fn fri_extension_challenges<E: Field + From<Goldilocks>>() {
+
+        let degree = 63;
+        let expansion_factor = 4;
+        let initial_codeword_length = (degree + 1) * expansion_factor;
+
+        let omega = Goldilocks::primitive_nth_root(initial_codeword_length as u128);
+        let fri: Fri<Goldilocks, E> = Fri::new(Goldilocks::generator(), omega, initial_codeword_length as u128, expansion_factor as u128, 17);
+
+        // base field codeword, folded with challenges drawn from the extension
+        let polynomial = Polynomial::new((0..degree + 1).map(|_| random_element::<Goldilocks>()).collect());
+        let domain = fri.eval_domain();
+        let mut codeword: Vec<E> = polynomial.evaluate_domain(&domain).into_iter().map(E::from).collect();
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        let points = fri.verify(&mut verifier_stream).expect("valid fri proof is rejected");
+
+        for (x, y) in points {
+            assert!(E::from(polynomial.evaluate(domain[x])) == y, "polynomial evaluates to wrong value");
+        }
+
+        for i in (0..degree * expansion_factor).step_by(expansion_factor) {
+            codeword[i] = E::zero();
+        }
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword, &mut proof_stream);
+
+        let mut verifier_stream = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_err(), "invalid fri proof is accepted");
+    }
+
+    #[test]
+    fn test_fri_extension_challenges() {
+        fri_extension_challenges::<GoldilocksExt2>();
+        fri_extension_challenges::<GoldilocksExt3>();
+    }
+}
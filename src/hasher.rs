@@ -0,0 +1,140 @@
+use std::fmt::Debug;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::algebra::Field;
+use crate::keccak::{keccak256, Keccak256, SpongeState, KECCAK_DELIMITER};
+
+// output of a hash function as it appears in Merkle trees and proofs
+pub trait Digest: Debug + Copy + Clone + PartialEq + Serialize + DeserializeOwned {
+
+    fn to_bytes(self) -> Vec<u8>;
+}
+
+impl Digest for [u8; 32] {
+
+    fn to_bytes(self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+// duplex sponge: absorbs the transcript as it grows and squeezes challenges on demand
+pub trait Sponge: Debug + Clone + Default {
+
+    fn absorb(&mut self, bytes: &[u8]);
+
+    // later absorbs continue from the state left by the squeeze
+    fn squeeze(&mut self) -> [u8; 32];
+}
+
+pub trait Hasher: Debug + Clone + PartialEq {
+
+    type Digest: Digest;
+
+    type Sponge: Sponge;
+
+    // identifies the hash function in serialized proofs, must be unique per hasher
+    const ID: u8;
+
+    fn hash(bytes: &[u8]) -> Self::Digest;
+
+    // compression of two child nodes into their parent
+    fn two_to_one(left: &Self::Digest, right: &Self::Digest) -> Self::Digest;
+
+    // leaf hash of a row of field elements
+    fn hash_elements<F: Field>(elements: &[F]) -> Self::Digest {
+        let bytes: Vec<u8> = elements.iter().flat_map(|e| e.to_bytes()).collect();
+        Self::hash(&bytes)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keccak;
+
+impl Hasher for Keccak {
+
+    type Digest = [u8; 32];
+
+    type Sponge = KeccakSponge;
+
+    const ID: u8 = 1;
+
+    fn hash(bytes: &[u8]) -> [u8; 32] {
+        keccak256(bytes)
+    }
+
+    fn two_to_one(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize()
+    }
+
+    // streams the encodings instead of concatenating the row first
+    fn hash_elements<F: Field>(elements: &[F]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        for e in elements.iter() {
+            hasher.update(&e.to_bytes());
+        }
+        hasher.finalize()
+    }
+}
+
+// duplex sponge over the keccak permutation at the keccak-256 rate
+#[derive(Clone, Debug)]
+pub struct KeccakSponge {
+    sponge: SpongeState,
+}
+
+impl KeccakSponge {
+    pub fn new() -> KeccakSponge {
+        KeccakSponge{sponge: SpongeState::new(136)}
+    }
+}
+
+impl Default for KeccakSponge {
+    fn default() -> KeccakSponge {
+        KeccakSponge::new()
+    }
+}
+
+impl Sponge for KeccakSponge {
+
+    fn absorb(&mut self, bytes: &[u8]) {
+        self.sponge.absorb(bytes);
+    }
+
+    // pads the absorbed input, permutes and outputs 32 bytes
+    fn squeeze(&mut self) -> [u8; 32] {
+        self.sponge.pad(KECCAK_DELIMITER);
+
+        let mut out = [0u8; 32];
+        self.sponge.squeeze(&mut out);
+        self.sponge.restart();
+        out
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::FieldElement;
+
+    #[test]
+    fn test_keccak_hasher() {
+
+        let a = Keccak::hash(b"left");
+        let b = Keccak::hash(b"right");
+        assert!(Keccak::two_to_one(&a, &b) == keccak256(&[a, b].concat()[..]), "compression is not keccak of the concatenation");
+        assert!(Keccak::two_to_one(&a, &b) != Keccak::two_to_one(&b, &a), "compression is symmetric");
+
+        let row = vec![FieldElement::new(1), FieldElement::new(2)];
+        assert!(Keccak::hash_elements(&row) == keccak256(&[row[0].to_le_bytes(), row[1].to_le_bytes()].concat()[..]), "element hash is not keccak of the encodings");
+
+        // squeezing an empty sponge twice gives two different challenges
+        let mut sponge = KeccakSponge::new();
+        let first = sponge.squeeze();
+        assert!(first != sponge.squeeze(), "sponge repeats a challenge");
+    }
+}
@@ -1,81 +1,384 @@
 
-use crate::algebra::FieldElement;
-use crate::keccak::keccak256;
+use std::marker::PhantomData;
+use crate::algebra::{Field, FieldElement};
+use crate::hasher::{Hasher, Keccak, Sponge};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Debug, Clone,Deserialize, Serialize, PartialEq)]
-pub enum Object {
+#[serde(bound = "F: Field, H: Hasher")]
+pub enum Object<F: Field = FieldElement, H: Hasher = Keccak> {
 
-    Point(FieldElement),
-    MerkleRoot([u8;32]),
-    CodeWord(Vec<FieldElement>),
-    ColinearityTest((FieldElement, FieldElement, FieldElement)),
-    MerklePath(Vec<[u8;32]>)
+    Point(F),
+    MerkleRoot(H::Digest),
+    CodeWord(Vec<F>),
+    ColinearityTest((F, F, F)),
+    MerklePath(Vec<H::Digest>),
+    MerkleMultiPath(Vec<H::Digest>),
+    Row(Vec<F>)
 }
 
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    // the proof bytes do not decode into a list of objects
+    Malformed,
+    // the proof does not start with the magic bytes
+    BadMagic,
+    // the proof was written in a format version this reader does not know
+    UnsupportedVersion,
+    // the proof is over a different field than the reader expects
+    FieldMismatch,
+    // the proof was committed with a different hash function
+    HashMismatch,
+    // the proof was made for different protocol parameters
+    ParameterMismatch,
+    // every object in the stream has already been pulled
+    Exhausted,
+    // the next object in the stream is of a different kind than requested
+    UnexpectedObject,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    // the proof stream could not supply the expected object
+    Proof(ProofError),
+    // the last FRI codeword has the wrong length or does not match its root
+    LastCodeword,
+    // the FRI low degree test rejected the codeword
+    LowDegree,
+    // a FRI colinearity test fails
+    Colinearity,
+    // a FRI leaf does not authenticate against its root
+    FriPath,
+    // a boundary quotient leaf does not authenticate against its root
+    BoundaryQuotientPath,
+    // a randomizer leaf does not authenticate against its root
+    RandomizerPath,
+    // the combination polynomial does not match the opened leafs
+    Combination,
+    // a boundary constraint names a cycle or register outside the trace
+    InvalidStatement,
+}
+
+impl From<ProofError> for VerifyError {
+    fn from(err: ProofError) -> VerifyError {
+        VerifyError::Proof(err)
+    }
+}
+
+
+// Proof format, all integers little endian:
+//
+//   magic       4 bytes   "STRK"
+//   version     u8        FORMAT_VERSION
+//   field id    u8        Field::ID of the objects in the stream
+//   hash id     u8        Hasher::ID of the Merkle and Fiat-Shamir hash
+//   sections    until the end of the proof, each one
+//     tag       u8        SECTION_PARAMETERS or SECTION_OBJECTS
+//     length    u32       number of body bytes
+//     body      length bytes
+//
+// The parameters section holds protocol parameters chosen by the prover (empty for
+// bare proof streams), the objects section holds the bincode encoding of the objects.
+// Readers skip sections with unknown tags. They also decode version 1 proofs, which are
+// the version byte followed by the objects without header or sections, and versions 2
+// to 4, which have the layout of version 5 with fewer object kinds. Older proofs can
+// only be inspected: the FRI and STARK verifiers reject them with UnsupportedVersion.
+pub const MAGIC: [u8; 4] = *b"STRK";
+
+// Version history:
+//
+//   1   version byte and bincode objects, no header
+//   2   header and sections
+//   3   FRI and STARK open leaves with one MerkleMultiPath per tree instead of a
+//       MerklePath per leaf, so version 2 transcripts no longer match the verifiers
+//   4   STARK boundary quotients are committed and opened by Row, one leaf holding
+//       every register, instead of one tree and one Point per register
+//   5   Merkle leaves and transcript prefixes encode field elements little-endian, in
+//       the byte order of the serialized objects, so version 4 roots no longer match;
+//       STARK transcripts start with the parameters and boundary constraints
+//
+// Migration: a proof cannot be converted to a later version, because the challenges
+// depend on the objects. Keep the witness and prove again with the current prover;
+// old proofs are rejected with UnsupportedVersion rather than failing on some object.
+pub const FORMAT_VERSION: u8 = 5;
+
+const LEGACY_VERSION: u8 = 1;
+
+// first version with header and sections, later ones only add object kinds
+const SECTIONED_VERSION: u8 = 2;
+
+pub const SECTION_PARAMETERS: u8 = 1;
+
+pub const SECTION_OBJECTS: u8 = 2;
+
 #[derive(Clone, Debug,Deserialize, Serialize)]
-pub struct ProofStream {
+#[serde(bound = "F: Field, H: Hasher")]
+pub struct ProofStream<F: Field = FieldElement, H: Hasher = Keccak> {
     
-    objects: Vec<Object>,
-    read_index: u8,
+    objects: Vec<Object<F, H>>,
+    read_index: usize,
+    // absorbs objects as they are pushed
+    #[serde(skip)]
+    prover_sponge: H::Sponge,
+    // absorbs objects as they are pulled
+    #[serde(skip)]
+    verifier_sponge: H::Sponge,
+    // format version the objects were read from
+    #[serde(skip, default = "current_version")]
+    version: u8,
 }
 
-impl ProofStream {
-    pub fn new() -> ProofStream {
-        return ProofStream{objects: vec![], read_index:0};
+fn current_version() -> u8 {
+    FORMAT_VERSION
+}
+
+impl<F: Field, H: Hasher> ProofStream<F, H> {
+    pub fn new() -> ProofStream<F, H> {
+        ProofStream::from_objects(vec![], FORMAT_VERSION)
     }
     
-    pub fn push(&mut self, obj: &Object) {
+    fn from_objects(objects: Vec<Object<F, H>>, version: u8) -> ProofStream<F, H> {
+        ProofStream{objects, read_index: 0, prover_sponge: H::Sponge::default(), verifier_sponge: H::Sponge::default(), version}
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn push(&mut self, obj: &Object<F, H>) {
+        self.prover_sponge.absorb(&bincode::serialize(obj).unwrap());
         self.objects.push(obj.clone())
     }
     
-    pub fn pull(&mut self) -> Object {
-        assert!((self.read_index as usize) < self.objects.len());
-        let obj = self.objects[self.read_index as usize].clone();
+    pub fn pull(&mut self) -> Result<Object<F, H>, ProofError> {
+        if self.read_index >= self.objects.len() {
+            return Err(ProofError::Exhausted);
+        }
+        let obj = self.objects[self.read_index].clone();
+        self.verifier_sponge.absorb(&bincode::serialize(&obj).unwrap());
         self.read_index += 1;
-        obj
+        Ok(obj)
+    }
+
+    pub fn pull_point(&mut self) -> Result<F, ProofError> {
+        match self.pull()? {
+            Object::Point(point) => Ok(point),
+            _ => Err(ProofError::UnexpectedObject),
+        }
+    }
+
+    pub fn pull_root(&mut self) -> Result<H::Digest, ProofError> {
+        match self.pull()? {
+            Object::MerkleRoot(root) => Ok(root),
+            _ => Err(ProofError::UnexpectedObject),
+        }
+    }
+
+    pub fn pull_codeword(&mut self) -> Result<Vec<F>, ProofError> {
+        match self.pull()? {
+            Object::CodeWord(codeword) => Ok(codeword),
+            _ => Err(ProofError::UnexpectedObject),
+        }
+    }
+
+    pub fn pull_colinearity_test(&mut self) -> Result<(F, F, F), ProofError> {
+        match self.pull()? {
+            Object::ColinearityTest(triple) => Ok(triple),
+            _ => Err(ProofError::UnexpectedObject),
+        }
+    }
+
+    pub fn pull_path(&mut self) -> Result<Vec<H::Digest>, ProofError> {
+        match self.pull()? {
+            Object::MerklePath(path) => Ok(path),
+            _ => Err(ProofError::UnexpectedObject),
+        }
+    }
+
+    pub fn pull_row(&mut self) -> Result<Vec<F>, ProofError> {
+        match self.pull()? {
+            Object::Row(row) => Ok(row),
+            _ => Err(ProofError::UnexpectedObject),
+        }
+    }
+
+    pub fn pull_multi_path(&mut self) -> Result<Vec<H::Digest>, ProofError> {
+        match self.pull()? {
+            Object::MerkleMultiPath(proof) => Ok(proof),
+            _ => Err(ProofError::UnexpectedObject),
+        }
     }
     
     
     pub fn serial(&self) -> Vec<u8> {
     
-        bincode::serialize(&self.objects).unwrap()
+        ProofWriter::new(&[]).write(self)
         
     }
     
-    pub fn deserial(bb: &Vec<u8>) -> ProofStream {
-        
-        let new_objects = bincode::deserialize(bb).unwrap();
+    pub fn deserial(bb: &[u8]) -> Result<ProofStream<F, H>, ProofError> {
         
-        ProofStream{objects: new_objects, read_index: 0} 
+        Ok(ProofReader::read(bb)?.into_stream())
     }
     
 
-    pub fn prover_fiat_shamir(&self) -> [u8;32] {
-        keccak256(&self.serial())
+    // binds data kept outside the proof, such as a signed message, into every later challenge,
+    // prover and verifier must bind the same prefix before the first push or pull
+    pub fn bind_prefix(&mut self, prefix: &[u8]) {
+        assert!(self.read_index == 0, "prefix bound after pulling");
+
+        // the length keeps prefix and transcript apart
+        let length = (prefix.len() as u64).to_le_bytes();
+        self.prover_sponge.absorb(&length);
+        self.prover_sponge.absorb(prefix);
+        self.verifier_sponge.absorb(&length);
+        self.verifier_sponge.absorb(prefix);
+    }
+
+    // challenge bound to every object pushed so far
+    pub fn prover_fiat_shamir(&mut self) -> [u8;32] {
+        self.prover_sponge.squeeze()
     }
     
-    pub fn verifier_fiat_shamir(&self) -> [u8;32] {
-        let verifier = ProofStream{objects: self.objects[..self.read_index as usize].to_vec(), read_index: 0};
-        keccak256(&verifier.serial())
+    // challenge bound to every object pulled so far
+    pub fn verifier_fiat_shamir(&mut self) -> [u8;32] {
+        self.verifier_sponge.squeeze()
+    }
+
+    // field element challenges, squeezed from the sponge of H like the byte challenges
+    pub fn prover_sample<E: Field>(&mut self) -> E {
+        E::sample(&mut self.prover_sponge)
+    }
+
+    pub fn verifier_sample<E: Field>(&mut self) -> E {
+        E::sample(&mut self.verifier_sponge)
     }
     
 }
 
 
+impl<F: Field, H: Hasher> Default for ProofStream<F, H> {
+    fn default() -> ProofStream<F, H> {
+        ProofStream::new()
+    }
+}
+
+
+// writes proof streams in the current format
+pub struct ProofWriter<F: Field = FieldElement, H: Hasher = Keccak> {
+    parameters: Vec<u8>,
+    field: PhantomData<F>,
+    hasher: PhantomData<H>,
+}
+
+impl<F: Field, H: Hasher> ProofWriter<F, H> {
+    pub fn new(parameters: &[u8]) -> ProofWriter<F, H> {
+        ProofWriter{parameters: parameters.to_vec(), field: PhantomData, hasher: PhantomData}
+    }
+
+    fn write_section(bytes: &mut Vec<u8>, tag: u8, body: &[u8]) {
+        assert!(body.len() <= u32::MAX as usize, "section does not fit the proof format");
+        bytes.push(tag);
+        bytes.extend((body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+    }
+
+    pub fn write(&self, stream: &ProofStream<F, H>) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        bytes.push(F::ID);
+        bytes.push(H::ID);
+
+        ProofWriter::<F, H>::write_section(&mut bytes, SECTION_PARAMETERS, &self.parameters);
+        ProofWriter::<F, H>::write_section(&mut bytes, SECTION_OBJECTS, &bincode::serialize(&stream.objects).unwrap());
+        bytes
+    }
+}
+
+// reads proofs written in the current or an older format
+#[derive(Debug)]
+pub struct ProofReader<F: Field = FieldElement, H: Hasher = Keccak> {
+    pub version: u8,
+    pub parameters: Vec<u8>,
+    stream: ProofStream<F, H>,
+}
+
+impl<F: Field, H: Hasher> ProofReader<F, H> {
+    pub fn read(bytes: &[u8]) -> Result<ProofReader<F, H>, ProofError> {
+
+        if bytes.first() == Some(&LEGACY_VERSION) {
+            // version 1 proofs were always committed with keccak
+            if H::ID != Keccak::ID {
+                return Err(ProofError::HashMismatch);
+            }
+            let objects = bincode::deserialize(&bytes[1..]).map_err(|_| ProofError::Malformed)?;
+            return Ok(ProofReader{version: LEGACY_VERSION, parameters: vec![], stream: ProofStream::from_objects(objects, LEGACY_VERSION)});
+        }
+
+        if bytes.len() < MAGIC.len() + 3 {
+            return Err(ProofError::Malformed);
+        }
+        if bytes[..MAGIC.len()] != MAGIC {
+            return Err(ProofError::BadMagic);
+        }
+
+        let (version, field_id, hash_id) = (bytes[4], bytes[5], bytes[6]);
+        if !(SECTIONED_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(ProofError::UnsupportedVersion);
+        }
+        if field_id != F::ID {
+            return Err(ProofError::FieldMismatch);
+        }
+        if hash_id != H::ID {
+            return Err(ProofError::HashMismatch);
+        }
+
+        let mut parameters: Option<Vec<u8>> = None;
+        let mut objects: Option<Vec<Object<F, H>>> = None;
+
+        let mut rest = &bytes[MAGIC.len() + 3..];
+        while !rest.is_empty() {
+            if rest.len() < 5 {
+                return Err(ProofError::Malformed);
+            }
+            let tag = rest[0];
+            let length = u32::from_le_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+            if rest.len() - 5 < length {
+                return Err(ProofError::Malformed);
+            }
+            let body = &rest[5..5 + length];
+            rest = &rest[5 + length..];
+
+            match tag {
+                SECTION_PARAMETERS if parameters.is_none() => parameters = Some(body.to_vec()),
+                SECTION_OBJECTS if objects.is_none() => {
+                    objects = Some(bincode::deserialize(body).map_err(|_| ProofError::Malformed)?);
+                }
+                SECTION_PARAMETERS | SECTION_OBJECTS => return Err(ProofError::Malformed),
+                // sections added by later minor revisions
+                _ => {}
+            }
+        }
+
+        let (Some(parameters), Some(objects)) = (parameters, objects) else { return Err(ProofError::Malformed) };
+        Ok(ProofReader{version, parameters, stream: ProofStream::from_objects(objects, version)})
+    }
+
+    pub fn into_stream(self) -> ProofStream<F, H> {
+        self.stream
+    }
+}
 
 
 #[cfg(test)]
 mod test {
-    use std::iter::Once;
-
     use super::*;
 
     #[test]
     fn test_serialize() {
 
-        let mut proof1 = ProofStream::new();
+        let mut proof1: ProofStream = ProofStream::new();
         proof1.push(&Object::Point(FieldElement::new(1)));
         proof1.push(&Object::MerkleRoot([1_u8; 32]));
         proof1.push(&Object::CodeWord(vec![FieldElement::new(1), FieldElement::new(2)]));
@@ -85,7 +388,7 @@ mod test {
 
 
         let ser = proof1.serial();
-        let mut proof2 = ProofStream::deserial(&ser);
+        let mut proof2 = ProofStream::deserial(&ser).unwrap();
 
         assert!(proof1.pull() == proof2.pull());
         assert!(proof1.pull() == proof2.pull());
@@ -93,8 +396,165 @@ mod test {
         assert!(proof1.pull() == proof2.pull());
         assert!(proof1.pull() == proof2.pull());
 
-        assert!(proof1.pull() == Object::Point(FieldElement::new(2)));
-        assert!(proof2.pull() == Object::Point(FieldElement::new(2)));
+        assert!(proof1.pull() == Ok(Object::Point(FieldElement::new(2))));
+        assert!(proof2.pull() == Ok(Object::Point(FieldElement::new(2))));
+    }
+
+    #[test]
+    fn test_pull_errors() {
+
+        let mut proof: ProofStream = ProofStream::new();
+        proof.push(&Object::MerkleRoot([1_u8; 32]));
+        proof.push(&Object::CodeWord(vec![FieldElement::new(1), FieldElement::new(2)]));
+
+        let bytes = proof.serial();
+        let mut verifier: ProofStream = ProofStream::deserial(&bytes).unwrap();
+        assert!(verifier.pull_root() == Ok([1_u8; 32]));
+        assert!(verifier.pull_path() == Err(ProofError::UnexpectedObject), "pulling the wrong kind succeeds");
+        assert!(verifier.pull_codeword() == Err(ProofError::Exhausted), "pulling past the end succeeds");
+
+        // truncated and garbage bytes are rejected instead of panicking
+        assert!(ProofStream::<FieldElement>::deserial(&bytes[..bytes.len() - 1]).is_err(), "truncated proof decodes");
+        assert!(ProofStream::<FieldElement>::deserial(&[&MAGIC[..], &[FORMAT_VERSION, FieldElement::ID, Keccak::ID, 0xff, 0xff]].concat()).is_err(), "garbage proof decodes");
+        assert!(ProofStream::<FieldElement>::deserial(&[0xff; 64]).err() == Some(ProofError::BadMagic), "proof without magic decodes");
+        assert!(ProofStream::<FieldElement>::deserial(&[]).is_err(), "empty proof decodes");
+    }
+
+    #[test]
+    fn test_format() {
+
+        let mut proof: ProofStream = ProofStream::new();
+        proof.push(&Object::MerkleRoot([3_u8; 32]));
+        proof.push(&Object::Point(FieldElement::new(5)));
+
+        let bytes = ProofWriter::new(&[9, 9, 9]).write(&proof);
+        assert!(bytes[..4] == MAGIC && bytes[4] == FORMAT_VERSION && bytes[5] == FieldElement::ID && bytes[6] == Keccak::ID, "header is wrong");
+
+        let reader: ProofReader = ProofReader::read(&bytes).unwrap();
+        assert!(reader.version == FORMAT_VERSION && reader.parameters == vec![9, 9, 9], "header does not round trip");
+        let mut stream = reader.into_stream();
+        assert!(stream.pull_root() == Ok([3_u8; 32]) && stream.pull_point() == Ok(FieldElement::new(5)), "objects do not round trip");
+
+        // unknown versions, other fields and other hashes are rejected
+        let mut future = bytes.clone();
+        future[4] = FORMAT_VERSION + 1;
+        assert!(ProofReader::<FieldElement>::read(&future).err() == Some(ProofError::UnsupportedVersion));
+
+        assert!(ProofReader::<crate::goldilocks::Goldilocks>::read(&bytes).err() == Some(ProofError::FieldMismatch));
+
+        let mut other_hash = bytes.clone();
+        other_hash[6] = Keccak::ID + 1;
+        assert!(ProofReader::<FieldElement>::read(&other_hash).err() == Some(ProofError::HashMismatch));
+
+        // sections with unknown tags are skipped
+        let mut extended = bytes.clone();
+        extended.extend([0x7f, 2, 0, 0, 0, 0xaa, 0xbb]);
+        assert!(ProofReader::<FieldElement>::read(&extended).is_ok(), "unknown section is not skipped");
+
+        // a section running past the end is rejected
+        let mut overlong = bytes.clone();
+        overlong.extend([0x7f, 200, 0, 0, 0, 0xaa]);
+        assert!(ProofReader::<FieldElement>::read(&overlong).err() == Some(ProofError::Malformed));
+
+        // a point encoded as its value plus the modulus is rejected
+        let objects = bincode::serialize(&vec![Object::<FieldElement>::Point(FieldElement::new(5))]).unwrap();
+        let mut unreduced = bytes[..7].to_vec();
+        unreduced.extend([SECTION_PARAMETERS, 0, 0, 0, 0, SECTION_OBJECTS]);
+        unreduced.extend((objects.len() as u32).to_le_bytes());
+        unreduced.extend(objects);
+        assert!(ProofReader::<FieldElement>::read(&unreduced).is_ok());
+
+        let len = unreduced.len();
+        unreduced[len - 16..].copy_from_slice(&(crate::algebra::P + 5).to_le_bytes());
+        assert!(ProofReader::<FieldElement>::read(&unreduced).err() == Some(ProofError::Malformed), "unreduced point decodes");
+
+        // versions 2 to 4 proofs remain readable
+        for version in SECTIONED_VERSION..FORMAT_VERSION {
+            let mut previous = bytes.clone();
+            previous[4] = version;
+            assert!(ProofReader::<FieldElement>::read(&previous).unwrap().version == version, "older proof does not read");
+        }
+
+        // version 1 proofs remain readable
+        let mut legacy = vec![1_u8];
+        legacy.extend(bincode::serialize(&vec![Object::<FieldElement>::MerkleRoot([3_u8; 32]), Object::Point(FieldElement::new(5))]).unwrap());
+        let mut stream: ProofStream = ProofStream::deserial(&legacy).unwrap();
+        assert!(stream.pull_root() == Ok([3_u8; 32]) && stream.pull_point() == Ok(FieldElement::new(5)), "version 1 proof does not read");
+    }
+
+    #[test]
+    fn test_large_stream() {
+
+        // far more objects than fit in a byte-sized read index
+        let n = 10_000;
+        let mut proof: ProofStream = ProofStream::new();
+        for i in 0..n {
+            proof.push(&Object::Point(FieldElement::new(i as u128)));
+        }
+        let prover_challenge = proof.prover_fiat_shamir();
+
+        let bytes = proof.serial();
+        assert!(bytes[4] == FORMAT_VERSION, "serialized stream does not carry the version tag");
+
+        let mut verifier: ProofStream = ProofStream::deserial(&bytes).unwrap();
+        for i in 0..n {
+            assert!(verifier.pull_point() == Ok(FieldElement::new(i as u128)), "large stream returns wrong object");
+        }
+        assert!(verifier.pull() == Err(ProofError::Exhausted));
+        assert!(verifier.verifier_fiat_shamir() == prover_challenge, "large stream derives different challenges");
+    }
+
+    #[test]
+    fn test_fiat_shamir() {
+
+        let objects: Vec<Object> = vec![
+            Object::MerkleRoot([1_u8; 32]),
+            Object::CodeWord((0..100).map(FieldElement::new).collect()),
+            Object::Point(FieldElement::new(7)),
+            Object::MerklePath(vec![[2_u8; 32]; 10]),
+        ];
+
+        // prover squeezes after every push, the verifier after every pull
+        let mut prover = ProofStream::new();
+        let mut prover_challenges: Vec<[u8;32]> = vec![];
+        for obj in objects.iter() {
+            prover.push(obj);
+            prover_challenges.push(prover.prover_fiat_shamir());
+        }
+
+        let mut verifier: ProofStream = ProofStream::deserial(&prover.serial()).unwrap();
+        let mut verifier_challenges: Vec<[u8;32]> = vec![];
+        for _ in 0..objects.len() {
+            verifier.pull().unwrap();
+            verifier_challenges.push(verifier.verifier_fiat_shamir());
+        }
+
+        assert!(prover_challenges == verifier_challenges, "prover and verifier derive different challenges");
+
+        // consecutive squeezes give fresh challenges
+        assert!(verifier.verifier_fiat_shamir() != verifier_challenges[objects.len() - 1], "squeezing twice repeats the challenge");
+
+        // a different transcript gives different challenges
+        let mut other: ProofStream = ProofStream::new();
+        other.push(&Object::MerkleRoot([0_u8; 32]));
+        assert!(other.prover_fiat_shamir() != prover_challenges[0], "challenge does not depend on transcript");
+
+        // a bound prefix changes every challenge and has to match on the verifier side
+        let mut prover: ProofStream = ProofStream::new();
+        prover.bind_prefix(b"message");
+        prover.push(&objects[0]);
+        let challenge = prover.prover_fiat_shamir();
+        assert!(challenge != prover_challenges[0], "prefix does not change the challenge");
+
+        let mut verifier: ProofStream = ProofStream::deserial(&prover.serial()).unwrap();
+        verifier.bind_prefix(b"message");
+        verifier.pull().unwrap();
+        assert!(verifier.verifier_fiat_shamir() == challenge, "same prefix gives a different challenge");
+
+        let mut verifier: ProofStream = ProofStream::deserial(&prover.serial()).unwrap();
+        verifier.bind_prefix(b"messagf");
+        verifier.pull().unwrap();
+        assert!(verifier.verifier_fiat_shamir() != challenge, "other prefix gives the same challenge");
     }
 
 }
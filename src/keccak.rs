@@ -1,5 +1,3 @@
-const P:u128 = 1 + 407 * ( 1 << 119 );
-
 const RC:[u64; 24] = [0x0000000000000001,
                     0x0000000000008082,
                     0x800000000000808a,
@@ -27,179 +25,383 @@ const RC:[u64; 24] = [0x0000000000000001,
 
 const RHO:[u32; 24] = [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44];
 
-const PI:[usize; 24] = [2, 11, 7, 13, 18, 15, 1, 8, 16, 9, 24, 20, 3, 19, 23, 17, 12, 10, 4, 22, 14, 21, 6, 5];
+// lane visited at each step of rho and pi, starting from lane 1
+const PI:[usize; 24] = [10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1];
+
+// keccak-f[1600] on 25 lanes, lane x + 5y holds state bytes 8(x + 5y)..8(x + 5y) + 8 little-endian
+pub fn keccak_f1600(a: &mut [u64; 25]) {
+
+    let mut c: [u64; 5] = [0; 5];
+
+    for rc in RC.iter() {
+
+        //theta
+        for x in 0..5 {
+            c[x] = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+        }
+
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                a[x + 5 * y] ^= d;
+            }
+        }
+
+        //rho and pi
+        let mut current = a[1];
+
+        for t in 0..24 {
+            (current, a[PI[t]]) = (a[PI[t]], current.rotate_left(RHO[t]));
+        }
 
-//byte-aligned 10*1 padding with 01 delimter 
-#[inline]
-fn pad( bytes: &mut [u8; 200], m: usize) { 
+        //chi
+        for y in 0..5 {
 
-    // q is the number of padding bytes needed 
-    let q = 136 - (m % 136);
-    bytes[m] = bytes[m] ^ 0x01;
-    bytes[m + (q-2) + 1] =  bytes[m + (q-2) + 1] ^ 0x80;
+            c.copy_from_slice(&a[5 * y..5 * y + 5]);
+
+            for x in 0..5 {
+                a[x + 5 * y] = c[x] ^ ((!c[(x + 1) % 5]) & c[(x + 2) % 5]);
+            }
+        }
 
+        //iota
+        a[0] ^= rc;
+    }
 }
 
-#[inline]
-fn to_little_endian(bytes: &[u8]) -> u64 {
-    let mut x:u64 = 0;
-    assert!(bytes.len() > 7);
 
-    //unrolled 
-    x += bytes[0] as u64;
-    x += (bytes[1] as u64) << (8) as u64;
-    x += (bytes[2] as u64) << (16) as u64;
-    x += (bytes[3] as u64) << (24) as u64;
-    x += (bytes[4] as u64) << (32) as u64;
-    x += (bytes[5] as u64) << (40) as u64;
-    x += (bytes[6] as u64) << (48) as u64;
-    x += (bytes[7] as u64) << (56) as u64;
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
 
-    x
+    let mut hasher = Keccak256::new();
+    hasher.update(bytes);
+    hasher.finalize()
 }
 
-#[inline]
-fn to_big_endian(bits: u64, index: usize) -> u8 {
 
-    let val: u8 = ((bits >> (8 * index)) % 256) as u8;
-    val
+// domain separation bytes, including the first bit of the 10*1 padding
+pub(crate) const KECCAK_DELIMITER: u8 = 0x01;
+const SHA3_DELIMITER: u8 = 0x06;
+const SHAKE_DELIMITER: u8 = 0x1f;
+
+// keccak sponge with a configurable rate, shared by every mode below
+#[derive(Clone, Debug)]
+pub(crate) struct SpongeState {
+    lanes: [u64; 25],
+    position: usize,
+    rate: usize,
 }
 
-// Converts s to state array a
-#[inline]
-fn to3d(s: &[u8; 200], a: &mut [u64; 25]) {
+impl SpongeState {
 
-    for x in 0..5 {
-        for y in 0..5 {
-            // Endianness is flipped to little 
-            a[(5*x)+y] = to_little_endian(&s[8*(x+5*y)..8*(x+5*y)+8]);
+    pub(crate) fn new(rate: usize) -> SpongeState {
+        SpongeState{lanes: [0; 25], position: 0, rate}
+    }
+
+    #[inline]
+    fn xor_byte(&mut self, index: usize, b: u8) {
+        self.lanes[index / 8] ^= (b as u64) << (8 * (index % 8));
+    }
+
+    pub(crate) fn absorb(&mut self, bytes: &[u8]) {
+
+        for b in bytes.iter() {
+            self.xor_byte(self.position, *b);
+            self.position += 1;
+
+            if self.position == self.rate {
+                keccak_f1600(&mut self.lanes);
+                self.position = 0;
+            }
         }
     }
-}
-// Converts state array a to s
-#[inline]
-fn to1d(s: &mut [u8; 200], a: &mut [u64; 25]) {
 
-    for x in 0..5 {
-        for y in 0..5{
+    // pads the last block and switches to squeezing
+    pub(crate) fn pad(&mut self, delimiter: u8) {
 
-                for byte in 0..8 {
-                    // Endianness is flipped to big
-                    s[8*(x+5*y)+byte] = to_big_endian(a[(5*x)+y], byte);
-                }
-            
+        self.xor_byte(self.position, delimiter);
+        self.xor_byte(self.rate - 1, 0x80);
+        keccak_f1600(&mut self.lanes);
+        self.position = 0;
+    }
+
+    pub(crate) fn squeeze(&mut self, out: &mut [u8]) {
+
+        for b in out.iter_mut() {
+            if self.position == self.rate {
+                keccak_f1600(&mut self.lanes);
+                self.position = 0;
+            }
+
+            *b = (self.lanes[self.position / 8] >> (8 * (self.position % 8))) as u8;
+            self.position += 1;
         }
     }
+
+    // duplexing: absorbing continues from the start of the block
+    pub(crate) fn restart(&mut self) {
+        self.position = 0;
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.lanes = [0; 25];
+        self.position = 0;
+    }
 }
 
-#[inline]
-fn hash_round(s: &mut [u8; 200]) {
-    // a state array 
-    // A 1d array is faster than 2d
-    let mut a: [u64; 25] = [0;25];
+// incremental keccak-256, input may be split into chunks of any size
+#[derive(Clone, Debug)]
+pub struct Keccak256 {
+    sponge: SpongeState,
+}
 
-    let mut c = [0,0,0,0,0];
+impl Keccak256 {
 
-    let mut d: [u64; 5] = [0;5];
+    pub fn new() -> Keccak256 {
+        Keccak256{sponge: SpongeState::new(136)}
+    }
 
-    let mut t: [u64; 5] = [0;5];
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.sponge.absorb(bytes);
+    }
 
-    to3d(s, &mut a);
+    // pads the absorbed input and returns its hash, the hasher is reset afterwards
+    pub fn finalize(&mut self) -> [u8; 32] {
 
-    for round in 0..24 {
-        
-        //theta
-        
-       for x in 0..5 {
-            c[x] = a[5*x] ^ a[(5*x)+1] ^ a[(5*x)+2]^ a[(5*x)+3]^ a[(5*x)+4];
-       }
-        
+        self.sponge.pad(KECCAK_DELIMITER);
 
-            for x in 0..5 {
-                d[x] = c[(x+4)% 5] ^ c[(x+1) % 5].rotate_left(1);
-            }
+        let mut hash: [u8; 32] = [0; 32];
+        self.sponge.squeeze(&mut hash);
 
+        self.reset();
+        hash
+    }
 
-      
-        for x in 0..5 {
+    // discards everything absorbed so far
+    pub fn reset(&mut self) {
+        self.sponge.reset();
+    }
+}
 
-                for y in 0..5 {
-                    a[(5*x)+y] ^= d[x];
-                }
-            
-        }
+impl Default for Keccak256 {
+    fn default() -> Keccak256 {
+        Keccak256::new()
+    }
+}
 
-        //rho and pi
-        let mut current = a[5];
+// FIPS 202 fixed length hash, the capacity is twice the output length
+fn sha3<const N: usize>(bytes: &[u8]) -> [u8; N] {
 
-            for t in 0..24{
-                (current, a[PI[t]]) = (a[PI[t]], current.rotate_left(RHO[t]));
-            
-            }
-        
+    let mut sponge = SpongeState::new(200 - 2 * N);
+    sponge.absorb(bytes);
+    sponge.pad(SHA3_DELIMITER);
 
-        //chi
-        for y in 0..5 {
+    let mut hash: [u8; N] = [0; N];
+    sponge.squeeze(&mut hash);
+    hash
+}
 
-            for x in 0..5 {
-                d[x] = a[(5*x)+y];
-            }
+pub fn sha3_224(bytes: &[u8]) -> [u8; 28] {
+    sha3::<28>(bytes)
+}
+
+pub fn sha3_256(bytes: &[u8]) -> [u8; 32] {
+    sha3::<32>(bytes)
+}
+
+pub fn sha3_384(bytes: &[u8]) -> [u8; 48] {
+    sha3::<48>(bytes)
+}
 
-                for x in 0..5 {
-                    a[(5*x)+y] = d[x] ^ (( !d[(x+1)%5]) & d[(x+2)%5]);
-                }
+pub fn sha3_512(bytes: &[u8]) -> [u8; 64] {
+    sha3::<64>(bytes)
+}
+
+// FIPS 202 extendable output function, absorb with update then squeeze as many bytes as needed
+#[derive(Clone, Debug)]
+pub struct Shake {
+    sponge: SpongeState,
+    squeezing: bool,
+}
+
+impl Shake {
+
+    pub fn shake128() -> Shake {
+        Shake{sponge: SpongeState::new(168), squeezing: false}
+    }
+
+    pub fn shake256() -> Shake {
+        Shake{sponge: SpongeState::new(136), squeezing: false}
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        assert!(!self.squeezing, "cannot absorb after squeezing");
+        self.sponge.absorb(bytes);
+    }
+
+    // consecutive calls continue the same output stream
+    pub fn squeeze(&mut self, out: &mut [u8]) {
 
+        if !self.squeezing {
+            self.sponge.pad(SHAKE_DELIMITER);
+            self.squeezing = true;
         }
-        
-        //iota
-        a[0] ^= RC[round];
+        self.sponge.squeeze(out);
+    }
 
+    pub fn reset(&mut self) {
+        self.sponge.reset();
+        self.squeezing = false;
     }
-    to1d(s, &mut a);
 }
 
+pub fn shake128(bytes: &[u8], length: usize) -> Vec<u8> {
+    let mut shake = Shake::shake128();
+    shake.update(bytes);
+    let mut out = vec![0; length];
+    shake.squeeze(&mut out);
+    out
+}
 
-pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+pub fn shake256(bytes: &[u8], length: usize) -> Vec<u8> {
+    let mut shake = Shake::shake256();
+    shake.update(bytes);
+    let mut out = vec![0; length];
+    shake.squeeze(&mut out);
+    out
+}
 
-    let mut s: [u8; 200] = [0; 200];
 
-    let mut offset: usize = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if bytes.len() % 136 == 0 {
-        for i in 0..(bytes.len()/136) as i64 -1{
-            
-            for j in 0..136 {
-                s[j] = s[j] ^ bytes[(i as usize *136)+j]     
-            }
-            hash_round(&mut s);
-
-            offset += 136;
-        } 
-        
-    } else {
-        for i in 0..bytes.len()/136 {
-            for j in 0..136{
-                s[j] =  s[j] ^ bytes[(i*136)+j];
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_streaming() {
+
+        let mut hasher = Keccak256::new();
+        assert!(hex(&hasher.finalize()) == "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470", "empty hash is wrong");
+
+        hasher.update(b"abc");
+        assert!(hex(&hasher.finalize()) == "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45", "abc hash is wrong");
+
+        // any chunking of the input gives the one-shot hash
+        let message: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+        let expected = keccak256(&message);
+
+        for chunk_size in [1, 7, 135, 136, 137, 500, 1000] {
+            for chunk in message.chunks(chunk_size) {
+                hasher.update(chunk);
             }
-            hash_round(&mut s);
-            
-            offset += 136;
+            assert!(hasher.finalize() == expected, "chunks of {} give a different hash", chunk_size);
         }
+
+        // reset drops the absorbed input
+        hasher.update(b"discarded");
+        hasher.reset();
+        hasher.update(&message);
+        assert!(hasher.finalize() == expected, "reset keeps absorbed input");
     }
 
-    for i in offset..bytes.len() {
-        s[i-offset] = s[i-offset] ^ bytes[i];
+    #[test]
+    fn test_sha3() {
+
+        assert!(hex(&sha3_224(b"")) == "6b4e03423667dbb73b6e15454f0eb1abd4597f9a1b078e3f5b5a6bc7");
+        assert!(hex(&sha3_256(b"")) == "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+        assert!(hex(&sha3_384(b"")) == "0c63a75b845e4f7d01107d852e4c2485c51a50aaaa94fc61995e71bbee983a2ac3713831264adb47fb6bd1e058d5f004");
+        assert!(hex(&sha3_512(b"")) == "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26");
+
+        assert!(hex(&sha3_224(b"abc")) == "e642824c3f8cf24ad09234ee7d3c766fc9a3a5168d0c94ad73b46fdf");
+        assert!(hex(&sha3_256(b"abc")) == "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+        assert!(hex(&sha3_384(b"abc")) == "ec01498288516fc926459f58e2c6ad8df9b473cb0fc08c2596da7cf0e49be4b298d88cea927ac7f539f1edf228376d25");
+        assert!(hex(&sha3_512(b"abc")) == "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0");
+
+        assert!(hex(&sha3_256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")) == "41c0dba2a9d6240849100376a8235e2c82e1b9998a999e21db32dd97496d3376");
+
+        // one million 'a', spans many blocks of every rate
+        let million = vec![b'a'; 1_000_000];
+        assert!(hex(&sha3_256(&million)) == "5c8875ae474a3634ba4fd55ec85bffd661f32aca75c6d699d0cdcb6c115891c1");
     }
 
-    // pad last block to 136 bytes
-    pad(&mut s, bytes.len() - offset);
+    #[test]
+    fn test_shake() {
 
-    hash_round(&mut s);
+        assert!(hex(&shake128(b"", 32)) == "7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef26");
+        assert!(hex(&shake256(b"", 64)) == "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762fd75dc4ddd8c0f200cb05019d67b592f6fc821c49479ab48640292eacb3b7c4be");
+        assert!(hex(&shake128(b"The quick brown fox jumps over the lazy dog", 32)) == "f4202e3c5852f9182a0430fd8144f0a74b95e7417ecae17db0f8cfeed0e3e66e");
 
-    let mut hash: [u8; 32] = [0; 32];
+        // a shorter output is a prefix of a longer one
+        let long = shake256(b"abc", 1000);
+        assert!(hex(&long[..64]) == "483366601360a8771c6863080cc4114d8db44530f8f1e1ee4f94ea37e78b5739d5a15bef186a5386c75744c0527e1faa9f8726e462a12a4feb06bd8801e751e4");
 
-    hash.copy_from_slice(&s[..32]);
+        // squeezing in pieces continues the same stream across block boundaries
+        let mut shake = Shake::shake256();
+        shake.update(b"a");
+        shake.update(b"bc");
+        let mut pieces = vec![0u8; 1000];
+        for chunk in pieces.chunks_mut(135) {
+            shake.squeeze(chunk);
+        }
+        assert!(pieces == long, "chunked squeeze differs");
 
-    hash
+        shake.reset();
+        shake.update(b"abc");
+        let mut again = vec![0u8; 1000];
+        shake.squeeze(&mut again);
+        assert!(again == long, "reset keeps state");
+    }
 
+    #[test]
+    fn test_keccak256_conformance() {
+
+        // ethereum vectors
+        assert!(hex(&keccak256(b"")) == "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        assert!(hex(&keccak256(b"abc")) == "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+        assert!(hex(&keccak256(b"The quick brown fox jumps over the lazy dog")) == "4d741b6f1eb29cb2a9b9911c82f56fa8d73b04959d3d9d222895df6c0b28aa15");
+        assert!(hex(&keccak256(b"transfer(address,uint256)")[..4]) == "a9059cbb", "erc20 selector is wrong");
+
+        // lengths around the 136 byte block boundary
+        let vectors = [
+            (1, "3ac225168df54212a25c1c01fd35bebfea408fdac2e31ddd6f80a4bbf9a5f1cb"),
+            (135, "34367dc248bbd832f4e3e69dfaac2f92638bd0bbd18f2912ba4ef454919cf446"),
+            (136, "a6c4d403279fe3e0af03729caada8374b5ca54d8065329a3ebcaeb4b60aa386e"),
+            (137, "d869f639c7046b4929fc92a4d988a8b22c55fbadb802c0c66ebcd484f1915f39"),
+            (271, "132f47effd6c8b1b299efa53fe68aece77ec8ae4eb2e294f668eec94f76001e1"),
+            (272, "cf7fcd4f705ee749930d19ca84561a9bf62516bd90a471545fa2f49fdc7e63c8"),
+            (273, "5a7b8187d2778e614097fac3097573de1fee4d972304d3360796a857029bb176"),
+            (408, "f763fe56a104e5c3ec0e4f311124923905d7f8e1dd03bdc91f2e6845ea523677"),
+            (1000, "b6a4ac1f51884d71f30fa397a5e155de3099e11fc0edef5d08b646e621e19de9"),
+        ];
+        for (length, expected) in vectors {
+            assert!(hex(&keccak256(&vec![b'a'; length])) == expected, "{} bytes hash wrong", length);
+        }
+
+        // long message that is not made of one repeated byte
+        let message: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+        assert!(hex(&keccak256(&message)) == "bdf0ff1eca7837dcbaeb98edb75f88860ff18b111101dc696fe07a6885e32848");
+
+        // every length up to a few blocks agrees with the streaming hasher fed byte by byte
+        let mut hasher = Keccak256::new();
+        for length in 0..3 * 136 + 2 {
+            for b in message[..length].iter() {
+                hasher.update(&[*b]);
+            }
+            assert!(hasher.finalize() == keccak256(&message[..length]), "{} bytes differ from streaming", length);
+        }
+    }
+
+    #[test]
+    fn test_keccak_f1600() {
+
+        // first lanes of the permuted all-zero state from the keccak reference
+        let mut lanes = [0u64; 25];
+        keccak_f1600(&mut lanes);
+        assert!(lanes[0] == 0xf1258f7940e1dde7 && lanes[1] == 0x84d5ccf933c0478a && lanes[24] == 0xeaf1ff7b5ceca249, "permutation of zero is wrong");
+
+        keccak_f1600(&mut lanes);
+        assert!(lanes[0] == 0x2d5c954df96ecb3c && lanes[1] == 0x6a332cd07057b56d, "second permutation of zero is wrong");
+    }
 }
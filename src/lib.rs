@@ -0,0 +1,15 @@
+pub mod univariate;
+pub mod algebra;
+pub mod merkle;
+pub mod keccak;
+pub mod hasher;
+pub mod ip;
+pub mod extension;
+pub mod fri;
+pub mod goldilocks;
+pub mod multivariate;
+pub mod ntt;
+pub mod stark;
+pub mod rescue_prime;
+pub mod poseidon;
+pub mod rpsss;
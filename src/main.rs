@@ -1,11 +1,3 @@
-mod univariate;
-mod algebra;
-mod merkle;
-mod keccak;
-mod ip;
-mod fri;
-
-
 fn main() {
 
 
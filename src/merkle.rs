@@ -1,122 +1,214 @@
-use crate::algebra::FieldElement;
-use crate::keccak::keccak256;
-
-#[derive(Debug)]
-pub struct Merkle {}
-
-impl Merkle {
-    
-    
-    pub fn commit_(leafs: Vec<[u8;32]>) -> Vec<[u8;32]> {
-        
-        assert!(leafs.len() & (leafs.len() -1) == 0 );
-        
-        if leafs.len() == 1 {
-            return [leafs[0]].to_vec()
-        } else {
-            
-            let first = leafs[leafs.len()/2..].to_vec();
-            let second = leafs[..leafs.len()/2].to_vec();
-            
-            //gross
-            let hash = keccak256(&[Merkle::commit_(second), Merkle::commit_(first)].concat()[..].concat()[..]).to_vec();
-            let result: Vec<[u8;32]> = hash.chunks_exact(32).map(|chunk| {
-                let mut array: [u8;32] = [0;32];
-                array.copy_from_slice(chunk);
-                array
-            }).collect();
-            result
-        }
+use crate::algebra::Field;
+use crate::hasher::{Hasher, Keccak};
+
+// complete binary tree in a flat array: node i has children 2i and 2i + 1,
+// the root is node 1 and leaf i is node n + i
+#[derive(Debug, Clone)]
+pub struct MerkleTree<H: Hasher = Keccak> {
+    nodes: Vec<H::Digest>,
+}
+
+impl<H: Hasher> MerkleTree<H> {
+
+    pub fn build<F: Field>(leafs: &[F]) -> MerkleTree<H> {
+        MerkleTree::from_leaf_hashes(leafs.iter().map(|leaf| H::hash_elements(&[*leaf])).collect())
     }
-    
-    pub fn commit(leafs: &Vec<FieldElement>) -> [u8;32] {
-        
-        let mut formatted_leafs: Vec<[u8;32]> = vec![];
-        
-        for i in 0..leafs.len() {
-            formatted_leafs.push(keccak256( &leafs[i].clone().value.to_be_bytes()[..] ));
-        }
-        
-        let root = Merkle::commit_(formatted_leafs); 
-        
-        return root[0].clone();
-        
-    }
-    
-     
-    pub fn open_(index: usize, leafs: Vec<[u8;32]> ) -> Vec<[u8;32]> {
-        
-        assert!(leafs.len() & (leafs.len()-1) == 0);
-        assert!(0 <= index && index < leafs.len());
-        
-        if leafs.len() == 2 {
-            return [leafs[1-index]].to_vec();
-        }
-        
-        if index < leafs.len()/2 {
-            
-            let first = leafs[leafs.len()/2..].to_vec();
-            let second = leafs[..leafs.len()/2].to_vec();
-            
-            return [Merkle::open_(index,second) , Merkle::commit_(first)].concat();
-            
-        }else {
-            
-            let first = leafs[leafs.len()/2..].to_vec();
-            let second = leafs[..leafs.len()/2].to_vec();
-            
-            return [Merkle::open_(index - leafs.len()/ 2 ,first) , Merkle::commit_(second)].concat();
+
+    // one leaf per row, a row of length one hashes like a single element
+    pub fn build_rows<F: Field>(rows: &[Vec<F>]) -> MerkleTree<H> {
+        MerkleTree::from_leaf_hashes(rows.iter().map(|row| H::hash_elements(row)).collect())
+    }
+
+    fn from_leaf_hashes(hashes: Vec<H::Digest>) -> MerkleTree<H> {
+
+        let n = hashes.len();
+        assert!(n > 0 && n & (n - 1) == 0, "number of leafs must be a power of two");
+
+        // node 0 is unused, the first leaf hash fills it
+        let mut nodes: Vec<H::Digest> = vec![hashes[0]; 2 * n];
+        nodes[n..].copy_from_slice(&hashes);
+        for i in (1..n).rev() {
+            nodes[i] = H::two_to_one(&nodes[2 * i], &nodes[2 * i + 1]);
         }
+
+        MerkleTree{nodes}
+    }
+
+    // a single leaf is its own root
+    pub fn root(&self) -> H::Digest {
+        self.nodes[1]
     }
-    
-    pub fn open(index: usize, leafs: &Vec<FieldElement> ) -> Vec<[u8;32]> {
-        
-        let mut formatted_leafs: Vec<[u8;32]> = vec![];  
-        
-         
-        for i in 0..leafs.len() {
-            formatted_leafs.push(keccak256( &leafs[i].clone().value.to_be_bytes()[..] ));
+
+    pub fn num_leafs(&self) -> usize {
+        self.nodes.len() / 2
+    }
+
+    // siblings of the nodes on the way from the leaf to the root, bottom up
+    pub fn open(&self, index: usize) -> Vec<H::Digest> {
+
+        assert!(index < self.num_leafs(), "cannot open leaf outside the tree");
+
+        let mut path: Vec<H::Digest> = vec![];
+        let mut node = self.num_leafs() + index;
+        while node > 1 {
+            path.push(self.nodes[node ^ 1]);
+            node >>= 1;
         }
-        
-        let path = Merkle::open_(index, formatted_leafs);
-        
         path
-        
-    }
-    
-    
-    pub fn verify_(root: [u8;32], index: usize, path: Vec<[u8;32]>, leaf: [u8;32]) -> bool {
-        
-        assert!(0 <= index && index < (1 << path.len()));
-        
-        if path.len() == 1 {
-            if index == 0{
-
-                return root == keccak256( &[leaf, path[0]].concat()[..])
-            
-            } else {
-            
-                return root == keccak256( &[path[0], leaf].concat()[..])
+    }
+
+    // sibling hashes needed to recompute the root from all leafs in indices, each node
+    // at most once, ordered level by level from the leafs up and by position within a level
+    pub fn open_many(&self, indices: &[usize]) -> Vec<H::Digest> {
+
+        let n = self.num_leafs();
+        let mut known: Vec<usize> = indices.iter().map(|i| {
+            assert!(*i < n, "cannot open leaf outside the tree");
+            n + i
+        }).collect();
+        known.sort();
+        known.dedup();
+
+        let mut proof: Vec<H::Digest> = vec![];
+        while !known.is_empty() && known[0] > 1 {
+            let mut parents: Vec<usize> = vec![];
+            let mut k = 0;
+            while k < known.len() {
+                let node = known[k];
+                if k + 1 < known.len() && known[k + 1] == node ^ 1 {
+                    // both children are known, nothing to send
+                    k += 2;
+                } else {
+                    proof.push(self.nodes[node ^ 1]);
+                    k += 1;
+                }
+                parents.push(node >> 1);
             }
-            
-        } else {
-            
-            if index % 2 == 0 {
-                return Merkle::verify_(root, index >>1, path[1..].to_vec(), keccak256(&[leaf, path[0]].concat()[..]))
+            known = parents;
+        }
+        proof
+    }
+}
+
+// Merkle tree over rows that keeps the rows, so openings return them with their paths
+#[derive(Debug, Clone)]
+pub struct RowTree<F: Field, H: Hasher = Keccak> {
+    rows: Vec<Vec<F>>,
+    tree: MerkleTree<H>,
+}
+
+impl<F: Field, H: Hasher> RowTree<F, H> {
+
+    pub fn build(rows: Vec<Vec<F>>) -> RowTree<F, H> {
+        let tree = MerkleTree::build_rows(&rows);
+        RowTree{rows, tree}
+    }
+
+    // rows of equal length from one codeword per column
+    pub fn from_columns(columns: &[Vec<F>]) -> RowTree<F, H> {
+        assert!(!columns.is_empty(), "cannot build rows from no columns");
+        let rows = (0..columns[0].len()).map(|i| columns.iter().map(|c| c[i]).collect()).collect();
+        RowTree::build(rows)
+    }
+
+    pub fn root(&self) -> H::Digest {
+        self.tree.root()
+    }
+
+    pub fn open(&self, index: usize) -> (Vec<F>, Vec<H::Digest>) {
+        (self.rows[index].clone(), self.tree.open(index))
+    }
+
+    pub fn open_many(&self, indices: &[usize]) -> (Vec<Vec<F>>, Vec<H::Digest>) {
+        (indices.iter().map(|i| self.rows[*i].clone()).collect(), self.tree.open_many(indices))
+    }
+}
+
+// checks that every leafs[k] sits at indices[k] in the tree with num_leafs leafs and the given root
+pub fn verify_many<H: Hasher, F: Field>(root: H::Digest, num_leafs: usize, indices: &[usize], leafs: &[F], proof: &[H::Digest]) -> bool {
+    let hashes: Vec<H::Digest> = leafs.iter().map(|leaf| H::hash_elements(&[*leaf])).collect();
+    verify_many_hashes::<H>(root, num_leafs, indices, &hashes, proof)
+}
+
+// checks that every rows[k] sits at indices[k] in the tree with num_leafs rows and the given root
+pub fn verify_rows_many<H: Hasher, F: Field>(root: H::Digest, num_leafs: usize, indices: &[usize], rows: &[Vec<F>], proof: &[H::Digest]) -> bool {
+    let hashes: Vec<H::Digest> = rows.iter().map(|row| H::hash_elements(row)).collect();
+    verify_many_hashes::<H>(root, num_leafs, indices, &hashes, proof)
+}
+
+fn verify_many_hashes<H: Hasher>(root: H::Digest, num_leafs: usize, indices: &[usize], hashes: &[H::Digest], proof: &[H::Digest]) -> bool {
+
+    if indices.is_empty() || indices.len() != hashes.len() || num_leafs == 0 || num_leafs & (num_leafs - 1) != 0 {
+        return false;
+    }
+    if indices.iter().any(|i| *i >= num_leafs) {
+        return false;
+    }
+
+    let mut known: Vec<(usize, H::Digest)> = indices.iter().zip(hashes.iter())
+        .map(|(i, hash)| (num_leafs + i, *hash))
+        .collect();
+    known.sort_by_key(|(node, _)| *node);
+
+    // an index opened twice must carry the same leaf both times
+    for k in 1..known.len() {
+        if known[k].0 == known[k - 1].0 && known[k].1 != known[k - 1].1 {
+            return false;
+        }
+    }
+    known.dedup_by_key(|(node, _)| *node);
+
+    let mut siblings = proof.iter();
+    while known[0].0 > 1 {
+        let mut parents: Vec<(usize, H::Digest)> = vec![];
+        let mut k = 0;
+        while k < known.len() {
+            let (node, hash) = known[k];
+            let (left, right) = if k + 1 < known.len() && known[k + 1].0 == node ^ 1 {
+                k += 2;
+                (hash, known[k - 1].1)
             } else {
-                return Merkle::verify_(root, index >>1, path[1..].to_vec(), keccak256(&[path[0], leaf].concat()[..]))
-            }
+                let Some(sibling) = siblings.next() else { return false };
+                k += 1;
+                if node % 2 == 0 { (hash, *sibling) } else { (*sibling, hash) }
+            };
+            parents.push((node >> 1, H::two_to_one(&left, &right)));
         }
+        known = parents;
+    }
+
+    // every sibling hash must be used
+    siblings.next().is_none() && known[0].1 == root
+}
+
+// checks that leaf sits at index in the tree with the given root
+pub fn verify<H: Hasher, F: Field>(root: H::Digest, index: usize, path: &[H::Digest], leaf: F) -> bool {
+    verify_hash::<H>(root, index, path, H::hash_elements(&[leaf]))
+}
+
+// checks that the whole row sits at index in the tree with the given root
+pub fn verify_row<H: Hasher, F: Field>(root: H::Digest, index: usize, path: &[H::Digest], row: &[F]) -> bool {
+    verify_hash::<H>(root, index, path, H::hash_elements(row))
+}
+
+fn verify_hash<H: Hasher>(root: H::Digest, index: usize, path: &[H::Digest], leaf_hash: H::Digest) -> bool {
+
+    // paths come from the prover, so reject instead of panicking on malformed ones
+    if path.len() >= usize::BITS as usize || index >= (1 << path.len()) {
+        return false;
     }
-    
-    pub fn verify(root: [u8;32], index: usize, path: Vec<[u8;32]>, leaf: FieldElement) -> bool {
-        
-        
-        let hash_leaf = keccak256( &leaf.value.to_be_bytes()[..]);
-        
-        return Merkle::verify_(root, index, path, hash_leaf);
+
+    let mut node = leaf_hash;
+    let mut position = index;
+    for sibling in path.iter() {
+        if position.is_multiple_of(2) {
+            node = H::two_to_one(&node, sibling);
+        } else {
+            node = H::two_to_one(sibling, &node);
+        }
+        position >>= 1;
     }
-    
+    node == root
 }
 
 
@@ -125,76 +217,176 @@ mod tests {
     use super::*;
     use rand::RngCore;
     use rand::rngs::OsRng;
-    use crate::algebra::FieldElement;
-    
+    use crate::algebra::{random_element, FieldElement};
 
-    
-    fn random_leaf() -> FieldElement {
-        let mut value = [0u8; 16];
-        OsRng.fill_bytes(&mut value);
-        
-        return FieldElement::new(u128::from_be_bytes(value));
+    // root of the leaf hashes computed by recursive halving, independent of the flat layout
+    fn recursive_root(hashes: &[[u8;32]]) -> [u8;32] {
+        if hashes.len() == 1 {
+            return hashes[0];
+        }
+        let half = hashes.len() / 2;
+        Keccak::two_to_one(&recursive_root(&hashes[..half]), &recursive_root(&hashes[half..]))
     }
 
     #[test]
     fn merkle() {
-        
-        let n:usize = 64;
-        
-        let mut leafs: Vec<FieldElement> = vec![];
-        
-        for i in 0..n {
-            leafs.push(random_leaf());
-        }
-        
-        let root = Merkle::commit(&leafs);
-        
+
+        let n: usize = 64;
+        let leafs: Vec<FieldElement> = (0..n).map(|_| random_element::<FieldElement>()).collect();
+        let tree: MerkleTree = MerkleTree::build(&leafs);
+        let root = tree.root();
+
         // opening any leaf should work
-        for i in 0..n {
-        
-            let path = Merkle::open(i,&leafs);
-            assert!(Merkle::verify(root, i, path, leafs[i]));
-        }
-        
-        // opening non-leafs should not work
-        
-        for i in 0..n {
-            let path = Merkle::open(i, &leafs);
-            assert!(false == Merkle::verify(root,i,path,random_leaf()));
+        for (i, leaf) in leafs.iter().enumerate() {
+            assert!(verify::<Keccak, _>(root, i, &tree.open(i), *leaf));
         }
-        
-        // opening wrong leafs should not work
-        
+
         for i in 0..n {
-            let path = Merkle::open(i, &leafs);
-            assert!(false == Merkle::verify(root, i, path, leafs[(i+1) % 64]));
+            let path = tree.open(i);
+
+            // opening non-leafs should not work
+            assert!(!verify::<Keccak, _>(root, i, &path, random_element::<FieldElement>()));
+
+            // opening wrong leafs should not work
+            assert!(!verify::<Keccak, _>(root, i, &path, leafs[(i + 1) % n]));
+
+            // opening leafs with the wrong index should not work
+            assert!(!verify::<Keccak, _>(root, (i + 1) % n, &path, leafs[i]));
+
+            // opening leafs to a false root should not work
+            let mut fake_root = [0u8; 32];
+            OsRng.fill_bytes(&mut fake_root);
+            assert!(!verify::<Keccak, _>(fake_root, i, &path, leafs[i]));
+
+            // opening leafs with even one falsehood in the path should not work
+            for j in 0..path.len() {
+                let mut fake_path = path.clone();
+                OsRng.fill_bytes(&mut fake_path[j]);
+                assert!(!verify::<Keccak, _>(root, i, &fake_path, leafs[i]));
+            }
         }
-        
-        // opening leafs with the wrong index should not work
-        for i in 0..n {
-            let path = Merkle::open(i, &leafs);
-            assert!(false == Merkle::verify(root, (i+1) % 64, path, leafs[i]));
+    }
+
+    #[test]
+    fn merkle_tree() {
+
+        for log_n in 0..8 {
+            let n: usize = 1 << log_n;
+            let leafs: Vec<FieldElement> = (0..n).map(|_| random_element::<FieldElement>()).collect();
+            let tree: MerkleTree = MerkleTree::build(&leafs);
+
+            // the flat tree agrees with the recursive commitment
+            let hashes: Vec<[u8;32]> = leafs.iter().map(|l| Keccak::hash_elements(&[*l])).collect();
+            assert!(tree.root() == recursive_root(&hashes), "flat root differs from recursive root");
+
+            for (i, leaf) in leafs.iter().enumerate() {
+                let path = tree.open(i);
+                assert!(path.len() == log_n, "path has the wrong length");
+
+                assert!(verify::<Keccak, _>(tree.root(), i, &path, *leaf), "valid opening is rejected");
+                assert!(!verify::<Keccak, _>(tree.root(), i, &path, random_element::<FieldElement>()), "wrong leaf is accepted");
+                assert!(n == 1 || !verify::<Keccak, _>(tree.root(), (i + 1) % n, &path, *leaf), "wrong index is accepted");
+                assert!(!verify::<Keccak, _>(tree.root(), i + n, &path, *leaf), "index outside the tree is accepted");
+            }
         }
 
-        //opening leafs to a false root should not work
-        for i in 0..n {
-            let path = Merkle::open(i, &leafs);
-            let mut fake_root = [0u8;32];
-            OsRng.fill_bytes(&mut fake_root);
-            assert!(false == Merkle::verify(fake_root, i, path, leafs[i]));
+        // truncated paths are rejected without panicking
+        let leafs: Vec<FieldElement> = (0..16).map(|_| random_element::<FieldElement>()).collect();
+        let tree: MerkleTree = MerkleTree::build(&leafs);
+        let path = tree.open(3);
+        assert!(!verify::<Keccak, _>(tree.root(), 3, &path[..2], leafs[3]), "truncated path is accepted");
+        assert!(!verify::<Keccak, _>(tree.root(), 3, &[], leafs[3]), "empty path is accepted");
+    }
+
+    #[test]
+    fn merkle_multi_proof() {
+
+        let n: usize = 256;
+        let leafs: Vec<FieldElement> = (0..n).map(|_| random_element::<FieldElement>()).collect();
+        let tree: MerkleTree = MerkleTree::build(&leafs);
+
+        for num_indices in [1, 2, 17, 64, 256] {
+            let mut indices: Vec<usize> = vec![];
+            for _ in 0..num_indices {
+                let mut b = [0u8; 8];
+                OsRng.fill_bytes(&mut b);
+                indices.push(u64::from_be_bytes(b) as usize % n);
+            }
+            let opened: Vec<FieldElement> = indices.iter().map(|i| leafs[*i]).collect();
+
+            let proof = tree.open_many(&indices);
+            assert!(verify_many::<Keccak, _>(tree.root(), n, &indices, &opened, &proof), "valid multi-proof is rejected");
+
+            // never larger than separate paths
+            assert!(proof.len() <= indices.len() * 8, "multi-proof is larger than separate paths");
+
+            // wrong leaf
+            let mut wrong = opened.clone();
+            wrong[0] = wrong[0] + FieldElement::one();
+            assert!(!verify_many::<Keccak, _>(tree.root(), n, &indices, &wrong, &proof), "wrong leaf is accepted");
+
+            // tampered, missing or surplus sibling hashes
+            if !proof.is_empty() {
+                let mut tampered = proof.clone();
+                tampered[proof.len() / 2][0] ^= 1;
+                assert!(!verify_many::<Keccak, _>(tree.root(), n, &indices, &opened, &tampered), "tampered proof is accepted");
+                assert!(!verify_many::<Keccak, _>(tree.root(), n, &indices, &opened, &proof[1..]), "short proof is accepted");
+            }
+            let mut long = proof.clone();
+            long.push([0u8; 32]);
+            assert!(!verify_many::<Keccak, _>(tree.root(), n, &indices, &opened, &long), "long proof is accepted");
         }
-        
-        // opening leafs with even one falsehood in the path should not work
+
+        // opening everything needs no siblings at all
+        let all: Vec<usize> = (0..n).collect();
+        assert!(tree.open_many(&all).is_empty());
+
+        // neighbouring leafs share all but one level
+        assert!(tree.open_many(&[4, 5]).len() == 7);
+
+        // duplicate indices must agree on the leaf
+        let proof = tree.open_many(&[3, 3]);
+        assert!(verify_many::<Keccak, _>(tree.root(), n, &[3, 3], &[leafs[3], leafs[3]], &proof));
+        assert!(!verify_many::<Keccak, _>(tree.root(), n, &[3, 3], &[leafs[3], leafs[4]], &proof), "conflicting duplicate leafs are accepted");
+
+        // malformed inputs are rejected without panicking
+        assert!(!verify_many::<Keccak, _>(tree.root(), n, &[n], &[leafs[0]], &proof));
+        assert!(!verify_many::<Keccak, _>(tree.root(), n, &[], &[] as &[FieldElement], &proof));
+        assert!(!verify_many::<Keccak, _>(tree.root(), n + 1, &[3], &[leafs[3]], &proof));
+    }
+
+    #[test]
+    fn merkle_rows() {
+
+        let n: usize = 32;
+        let width: usize = 3;
+        let columns: Vec<Vec<FieldElement>> = (0..width).map(|_| (0..n).map(|_| random_element::<FieldElement>()).collect()).collect();
+        let tree: RowTree<FieldElement> = RowTree::from_columns(&columns);
+
+        // single-element rows commit like plain leafs
+        let singletons: Vec<Vec<FieldElement>> = columns[0].iter().map(|l| vec![*l]).collect();
+        assert!(MerkleTree::<Keccak>::build_rows(&singletons).root() == MerkleTree::<Keccak>::build(&columns[0]).root(), "singleton rows differ from leafs");
+
         for i in 0..n {
-            let path = Merkle::open(i, &leafs);
-            for j in 0..path.len() {
-                let mut fake_index = [0u8;32];
-                OsRng.fill_bytes(&mut fake_index);
-                
-                let mut fake_path = path.clone();
-                fake_path[j] = fake_index;
-                assert!(false == Merkle::verify(root, i, fake_path, leafs[i]));
+            let (row, path) = tree.open(i);
+            assert!(row == columns.iter().map(|column| column[i]).collect::<Vec<_>>(), "open returns the wrong row");
+            assert!(verify_row::<Keccak, _>(tree.root(), i, &path, &row), "valid row is rejected");
+
+            // changing any register or dropping one breaks the path
+            for c in 0..width {
+                let mut wrong = row.clone();
+                wrong[c] = wrong[c] + FieldElement::one();
+                assert!(!verify_row::<Keccak, _>(tree.root(), i, &path, &wrong), "wrong row is accepted");
             }
+            assert!(!verify_row::<Keccak, _>(tree.root(), i, &path, &row[..width - 1]), "truncated row is accepted");
         }
+
+        let indices = vec![1, 2, 9, 30];
+        let (rows, proof) = tree.open_many(&indices);
+        assert!(verify_rows_many::<Keccak, _>(tree.root(), n, &indices, &rows, &proof), "valid rows are rejected");
+
+        let mut wrong = rows.clone();
+        wrong[2][1] = random_element::<FieldElement>();
+        assert!(!verify_rows_many::<Keccak, _>(tree.root(), n, &indices, &wrong, &proof), "wrong rows are accepted");
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,12 @@
-
-/* 
 use std::collections::HashMap;
+use std::ops;
+use crate::algebra::FieldElement;
+use crate::univariate::Polynomial;
+
 #[derive(Debug, Clone)]
 pub struct MPolynomial {
 
-    dictionary: HashMap<Vec<usize>,FieldElement>
+    pub dictionary: HashMap<Vec<usize>,FieldElement>
     
 }
 
@@ -13,32 +15,32 @@ impl MPolynomial {
     
     
     pub fn new(d: HashMap<Vec<usize>, FieldElement>) -> Self {
-        return MPolynomial{dictionary: d}
+        MPolynomial{dictionary: d}
     }
     
     
     pub fn zero() -> Self {
-        return MPolynomial{dictionary: HashMap::new()}
+        MPolynomial{dictionary: HashMap::new()}
     }
     
     pub fn constant(element: FieldElement) -> Self {
         let mut d: HashMap<Vec<usize>,FieldElement> = HashMap::new();    
         d.insert(vec![0], element);
         
-        return MPolynomial::new(d);
+        MPolynomial::new(d)
     }
     
     pub fn is_zero(&self) -> bool {
         
         if self.dictionary.is_empty() {
-            return true;
+            true
         } else {
             for val in self.dictionary.values() {
-                if val.is_zero() == false {
+                if !val.is_zero() {
                     return false;
                 }
             }
-            return true;
+            true
         }
     }
     
@@ -74,28 +76,34 @@ impl MPolynomial {
         acc
     }
     
-    pub fn evaluate(self, point:Vec<FieldElement>) -> FieldElement {
+    pub fn evaluate(&self, point: &[FieldElement]) -> FieldElement {
         
         let mut acc = FieldElement::zero();
         for (k, v) in self.dictionary.iter() {
             
-            let mut prod = v.clone();
+            let mut prod = *v;
             
             for i in 0..k.len() {
-                prod = prod.clone() * (point[i].clone() ^ k[i] as u128);
+                if k[i] == 0 {
+                    continue;
+                }
+                prod = prod * (point[i] ^ k[i] as u128);
             }
             acc = acc + prod;
         }
         acc
     }
     
-    pub fn evaluate_symbolic(self, point:Vec<Polynomial>) -> Polynomial {
+    pub fn evaluate_symbolic(&self, point: &[Polynomial]) -> Polynomial {
         let mut acc = Polynomial::new(vec![]);
         
         for (k,v) in self.dictionary.iter() {
-            let mut prod = Polynomial::new(vec![v.clone()]);
+            let mut prod = Polynomial::new(vec![*v]);
             
             for i in 0..k.len() {
+                if k[i] == 0 {
+                    continue;
+                }
                 prod = prod.clone() * (point[i].clone() ^ k[i] as u128)
             }
             
@@ -106,6 +114,13 @@ impl MPolynomial {
     }
 }
 
+impl PartialEq for MPolynomial {
+
+    fn eq(&self, other: &Self) -> bool {
+        (self.clone() - other.clone()).is_zero()
+    }
+}
+
 impl ops::Add for MPolynomial {
     type Output = MPolynomial;
     
@@ -127,20 +142,23 @@ impl ops::Add for MPolynomial {
         }
         
         for (k, v) in self.dictionary.iter() {
-            let mut pad:Vec<usize> = [k.clone(), vec![0; num_variables- k.len()]].concat();
-            d.insert(pad,v.clone());
+            let pad:Vec<usize> = [k.clone(), vec![0; num_variables- k.len()]].concat();
+            d.insert(pad,*v);
         }
         for (k, v) in other.dictionary.iter() {
-            let mut pad:Vec<usize> = [k.clone(), vec![0; num_variables- k.len()]].concat();
+            let pad:Vec<usize> = [k.clone(), vec![0; num_variables- k.len()]].concat();
             
             if d.contains_key(&pad) {
                 
-                let f = d.get(&pad).unwrap().clone() + v.clone();
+                let f = *d.get(&pad).unwrap() + *v;
                 d.insert(pad,  f);
             } else {
-                d.insert(pad,v.clone());
+                d.insert(pad,*v);
             }
         }
+        
+        // cancelled terms are dropped, so every key counts towards the degree
+        d.retain(|_, v| !v.is_zero());
     
         MPolynomial::new(d)
     }
@@ -179,13 +197,15 @@ impl ops::Mul for MPolynomial {
                 }
                 
                 if d.contains_key(&exponent) {
-                    let f = d.get(&exponent).unwrap().clone() + v0.clone() * v1.clone();
-                    
+                    let f = *d.get(&exponent).unwrap() + *v0 * *v1;
+                    d.insert(exponent, f);
                 } else {
-                    d.insert(exponent, v0.clone() * v1.clone());
+                    d.insert(exponent, *v0 * *v1);
                 }
             }
         } 
+        
+        d.retain(|_, v| !v.is_zero());
          
         MPolynomial::new(d)
     }
@@ -197,7 +217,7 @@ impl ops::Sub for MPolynomial {
     
     fn sub(self, other: MPolynomial) -> MPolynomial {
         
-        return self + (-other);
+        self + (-other)
     }
 }
 
@@ -209,9 +229,10 @@ impl ops::Neg for MPolynomial {
         
         for (k, v) in self.dictionary.iter() {
             
-            d.insert(k.clone(), -v.clone());
+            d.insert(k.clone(), -*v);
            
         }
+        d.retain(|_, v| !v.is_zero());
         MPolynomial::new(d)
     }
 }
@@ -220,14 +241,18 @@ impl ops::BitXor<u128> for MPolynomial {
     type Output = MPolynomial;
     
     fn bitxor (self, exponent:u128) -> Self {
+        if exponent == 0 {
+            return MPolynomial::constant(FieldElement::one());
+        }
         if self.is_zero() {
             return MPolynomial::zero();
         }
         
         let mut num_variables:usize = 0; 
         for key in self.dictionary.keys() {
-            num_variables = key.len();
-            break;
+            if key.len() > num_variables {
+                num_variables = key.len();
+            }
         }
         
         let exp = vec![0;num_variables];
@@ -247,4 +272,119 @@ impl ops::BitXor<u128> for MPolynomial {
     }
 }
 
-*/
\ No newline at end of file
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::algebra::FieldElement;
+
+    #[test]
+    pub fn test_evaluate() {
+
+        let variables = MPolynomial::variables(4);
+        let zero = FieldElement::zero();
+        let one = FieldElement::one();
+        let two = FieldElement::new(2);
+        let five = FieldElement::new(5);
+
+        let mpoly1 = MPolynomial::constant(one) * variables[0].clone() + MPolynomial::constant(two) * variables[1].clone() + MPolynomial::constant(five) * (variables[2].clone() ^ 3);
+        let mpoly2 = MPolynomial::constant(one) * variables[0].clone() * variables[3].clone() + MPolynomial::constant(five) * (variables[3].clone() ^ 3) + MPolynomial::constant(five);
+
+        let mpoly3 = mpoly1.clone() * mpoly2.clone();
+
+        let point = vec![zero, five, five, two];
+
+        let eval1 = mpoly1.evaluate(&point);
+        let eval2 = mpoly2.evaluate(&point);
+        let eval3 = mpoly3.evaluate(&point);
+
+        assert!(eval1 * eval2 == eval3, "multivariate polynomial multiplication does not commute with evaluation");
+        assert!(eval1 + eval2 == (mpoly1.clone() + mpoly2.clone()).evaluate(&point), "multivariate polynomial addition does not commute with evaluation");
+        assert!(eval1 - eval2 == (mpoly1 - mpoly2).evaluate(&point), "multivariate polynomial subtraction does not commute with evaluation");
+
+        println!("multivariate evaluate test success \\o/");
+    }
+
+    #[test]
+    pub fn test_distributivity() {
+
+        let variables = MPolynomial::variables(3);
+        let two = FieldElement::new(2);
+        let five = FieldElement::new(5);
+
+        let a = MPolynomial::constant(five) * variables[0].clone() * variables[1].clone() + variables[2].clone();
+        let b = (variables[0].clone() ^ 2) + MPolynomial::constant(two);
+        let c = MPolynomial::constant(two) * variables[1].clone() - (variables[2].clone() ^ 3);
+
+        let lhs = a.clone() * (b.clone() + c.clone());
+        let rhs = a.clone() * b.clone() + a.clone() * c.clone();
+
+        assert!(lhs == rhs, "multivariate polynomial multiplication does not distribute over addition");
+
+        // powers should agree with repeated multiplication
+        assert!((a.clone() ^ 3) == a.clone() * a.clone() * a.clone(), "multivariate polynomial exponentiation fails");
+        assert!((a.clone() ^ 0) == MPolynomial::constant(FieldElement::one()), "multivariate polynomial exponentiation fails");
+
+        // subtracting a polynomial from itself gives zero
+        assert!((a.clone() - a.clone()).is_zero(), "multivariate polynomial negation fails");
+        assert!(-(-a.clone()) == a, "multivariate polynomial negation fails");
+
+        println!("multivariate polynomial distributivity success \\o/");
+    }
+
+    #[test]
+    pub fn test_lift() {
+
+        let zero = FieldElement::zero();
+        let one = FieldElement::one();
+        let two = FieldElement::new(2);
+        let five = FieldElement::new(5);
+
+        let upoly = Polynomial::interpolate_domain(&[zero, one, two], &[two, five, five]);
+        let mpoly = MPolynomial::lift(upoly.clone(), 3);
+
+        assert!(upoly.evaluate(five) == mpoly.evaluate(&[zero, zero, zero, five]), "lifting univariate to multivariate failed");
+
+        println!("lifting univariate to multivariate polynomial success \\o/");
+    }
+
+    #[test]
+    pub fn test_evaluate_symbolic() {
+
+        let variables = MPolynomial::variables(3);
+        let two = FieldElement::new(2);
+        let five = FieldElement::new(5);
+
+        let mpoly = variables[0].clone() * (variables[1].clone() ^ 2) + MPolynomial::constant(five) * variables[2].clone() + MPolynomial::constant(two);
+
+        let x = Polynomial::new(vec![FieldElement::zero(), FieldElement::one()]);
+        let point = vec![x.clone(), x.clone() + Polynomial::new(vec![two]), x.clone() ^ 3];
+
+        let upoly = mpoly.evaluate_symbolic(&point);
+
+        // evaluating symbolically then at a value matches evaluating at the values directly
+        for i in 0..10 {
+            let z = FieldElement::new(i * 17 + 3);
+            let values: Vec<FieldElement> = point.iter().map(|p| p.evaluate(z)).collect();
+            assert!(upoly.evaluate(z) == mpoly.evaluate(&values), "symbolic evaluation does not match evaluation");
+        }
+
+        println!("multivariate symbolic evaluation success \\o/");
+    }
+
+    #[test]
+    pub fn test_cancellation() {
+
+        let variables = MPolynomial::variables(2);
+        let (x, y) = (variables[0].clone(), variables[1].clone());
+
+        // terms that cancel leave no zero coefficients behind
+        let difference = (x.clone() ^ 3) + y.clone() - (x.clone() ^ 3);
+        assert!(difference.dictionary.len() == 1 && difference.dictionary.contains_key(&vec![0, 1]), "cancelled term is kept");
+
+        let product = (x.clone() + y.clone()) * (x.clone() - y.clone());
+        assert!(!product.dictionary.contains_key(&vec![1, 1]), "cancelled cross term is kept");
+        assert!((x.clone() - x.clone()).dictionary.is_empty(), "difference of equal polynomials is not empty");
+    }
+}
@@ -0,0 +1,146 @@
+use crate::algebra::Field;
+
+// reorders values so that entry i moves to the bit-reversal of i
+fn bit_reverse<F: Field>(values: &mut [F]) {
+    let n = values.len();
+    let log_n = n.trailing_zeros();
+
+    if n <= 2 {
+        return;
+    }
+
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - log_n);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+}
+
+// evaluates the polynomial with coefficients `values` in the powers of `primitive_root`
+pub fn ntt<F: Field>(primitive_root: F, values: &[F]) -> Vec<F> {
+
+    let n = values.len();
+    let one = F::one();
+
+    assert!(n > 0 && n & (n - 1) == 0, "cannot compute ntt of non-power-of-two sequence");
+    assert!(primitive_root ^ n as u128 == one, "primitive root must be nth root of unity");
+    assert!(n == 1 || primitive_root ^ (n / 2) as u128 != one, "primitive root is not primitive nth root of unity");
+
+    let mut a = values.to_vec();
+    bit_reverse(&mut a);
+
+    let mut len = 2;
+    while len <= n {
+
+        let w_len = primitive_root ^ (n / len) as u128;
+
+        for start in (0..n).step_by(len) {
+            let mut w = one;
+            for j in 0..len / 2 {
+                let u = a[start + j];
+                let v = a[start + j + len / 2] * w;
+                a[start + j] = u + v;
+                a[start + j + len / 2] = u - v;
+                w = w * w_len;
+            }
+        }
+        len <<= 1;
+    }
+
+    a
+}
+
+// recovers the coefficients of the polynomial that takes `values` in the powers of `primitive_root`
+pub fn intt<F: Field>(primitive_root: F, values: &[F]) -> Vec<F> {
+
+    if values.len() == 1 {
+        return values.to_vec();
+    }
+
+    let ninv = F::new(values.len() as u128).inverse();
+    let transformed = ntt(primitive_root.inverse(), values);
+
+    transformed.into_iter().map(|t| ninv * t).collect()
+}
+
+// evaluates the polynomial with coefficients `values` in offset * primitive_root^i
+pub fn coset_ntt<F: Field>(offset: F, primitive_root: F, values: &[F]) -> Vec<F> {
+
+    let mut scaled: Vec<F> = vec![];
+    let mut offset_i = F::one();
+
+    for value in values.iter() {
+        scaled.push(*value * offset_i);
+        offset_i = offset_i * offset;
+    }
+
+    ntt(primitive_root, &scaled)
+}
+
+// recovers the coefficients of the polynomial that takes `values` in offset * primitive_root^i
+pub fn coset_intt<F: Field>(offset: F, primitive_root: F, values: &[F]) -> Vec<F> {
+
+    let mut coefficients = intt(primitive_root, values);
+    let offset_inv = offset.inverse();
+    let mut offset_i = F::one();
+
+    for coefficient in coefficients.iter_mut() {
+        *coefficient = *coefficient * offset_i;
+        offset_i = offset_i * offset_inv;
+    }
+
+    coefficients
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::algebra::{random_element, FieldElement};
+    use crate::univariate::Polynomial;
+
+    #[test]
+    pub fn test_ntt() {
+
+        for logn in 0..7 {
+            let n: usize = 1 << logn;
+            let primitive_root = FieldElement::generator().primitive_nth_root(n as u128);
+
+            let coefficients: Vec<FieldElement> = (0..n).map(|_| random_element()).collect();
+            let poly = Polynomial::new(coefficients.clone());
+
+            let values = ntt(primitive_root, &coefficients);
+
+            for (i, value) in values.iter().enumerate() {
+                assert!(*value == poly.evaluate(primitive_root ^ i as u128), "ntt does not match evaluation");
+            }
+
+            assert!(intt(primitive_root, &values) == coefficients, "inverse ntt does not invert ntt");
+        }
+
+        println!("ntt success \\o/");
+    }
+
+    #[test]
+    pub fn test_coset_ntt() {
+
+        let n: usize = 64;
+        let primitive_root = FieldElement::generator().primitive_nth_root(n as u128);
+        let offset = FieldElement::generator();
+
+        let coefficients: Vec<FieldElement> = (0..n).map(|_| random_element()).collect();
+        let poly = Polynomial::new(coefficients.clone());
+
+        let values = coset_ntt(offset, primitive_root, &coefficients);
+
+        for (i, value) in values.iter().enumerate() {
+            assert!(*value == poly.evaluate(offset * (primitive_root ^ i as u128)), "coset ntt does not match evaluation");
+        }
+
+        assert!(coset_intt(offset, primitive_root, &values) == coefficients, "inverse coset ntt does not invert coset ntt");
+
+        println!("coset ntt success \\o/");
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::OnceLock;
+use crate::algebra::{Field, FieldElement, P};
+use crate::hasher::{Digest, Hasher, Sponge};
+use crate::keccak::shake256;
+
+// Poseidon permutation over the 128-bit field with the x^3 S-box, 3 being the smallest exponent coprime to p - 1
+#[derive(Debug, Clone)]
+pub struct Poseidon {
+    pub width: usize,
+    pub full_rounds: usize,
+    pub partial_rounds: usize,
+    round_constants: Vec<FieldElement>,
+    mds: Vec<Vec<FieldElement>>,
+}
+
+const ALPHA: u128 = 3;
+
+impl Poseidon {
+
+    pub fn new(width: usize, full_rounds: usize, partial_rounds: usize) -> Poseidon {
+
+        assert!(width >= 2, "width must leave room for rate and capacity");
+        assert!(full_rounds.is_multiple_of(2), "full rounds are split evenly around the partial rounds");
+
+        Poseidon{width, full_rounds, partial_rounds,
+            round_constants: Poseidon::round_constants(width, full_rounds, partial_rounds),
+            mds: Poseidon::mds(width)}
+    }
+
+    // one constant per register and round, 17 little-endian bytes of SHAKE256 each so the reduction is nearly uniform
+    fn round_constants(width: usize, full_rounds: usize, partial_rounds: usize) -> Vec<FieldElement> {
+
+        let bytes_per_int = 17;
+        let seed = format!("Poseidon({},{},{},{})", P, width, full_rounds, partial_rounds);
+        let bytes = shake256(seed.as_bytes(), bytes_per_int * width * (full_rounds + partial_rounds));
+
+        let shift = FieldElement::new(2) ^ 128;
+        bytes.chunks(bytes_per_int).map(|chunk| {
+            let mut low = [0u8; 16];
+            low.copy_from_slice(&chunk[..16]);
+            FieldElement::new(u128::from_le_bytes(low)) + FieldElement::new(chunk[16] as u128) * shift
+        }).collect()
+    }
+
+    // cauchy matrix 1 / (x_i + y_j) with x_i = i and y_j = width + j, every square submatrix is invertible
+    fn mds(width: usize) -> Vec<Vec<FieldElement>> {
+
+        let denominators: Vec<FieldElement> = (0..width * width).map(|k| FieldElement::new((k / width + width + k % width) as u128)).collect();
+        let inverses = FieldElement::batch_inverse(&denominators);
+        inverses.chunks(width).map(|row| row.to_vec()).collect()
+    }
+
+    pub fn permute(&self, state: &mut [FieldElement]) {
+
+        assert!(state.len() == self.width, "state has the wrong width");
+
+        let half = self.full_rounds / 2;
+        for r in 0..self.full_rounds + self.partial_rounds {
+
+            for (s, c) in state.iter_mut().zip(&self.round_constants[r * self.width..(r + 1) * self.width]) {
+                *s = *s + *c;
+            }
+
+            // partial rounds only apply the S-box to the first register
+            if r < half || r >= half + self.partial_rounds {
+                for s in state.iter_mut() {
+                    *s = *s ^ ALPHA;
+                }
+            } else {
+                state[0] = state[0] ^ ALPHA;
+            }
+
+            let mixed: Vec<FieldElement> = self.mds.iter().map(|row| {
+                let mut acc = FieldElement::zero();
+                for j in 0..self.width {
+                    acc = acc + row[j] * state[j];
+                }
+                acc
+            }).collect();
+            state.copy_from_slice(&mixed);
+        }
+    }
+}
+
+// width 3 with 8 full and 84 partial rounds as in the paper's x^3 instances, conservative for a 128-bit field
+pub fn default_poseidon() -> &'static Poseidon {
+    static POSEIDON: OnceLock<Poseidon> = OnceLock::new();
+    POSEIDON.get_or_init(|| Poseidon::new(3, 8, 84))
+}
+
+// bytes per element when packing bytes, 15 bytes always stay below p
+const BYTES_PER_ELEMENT: usize = 15;
+
+// duplex sponge over the permutation, the last register is the capacity
+#[derive(Debug, Clone)]
+pub struct PoseidonSponge {
+    state: Vec<FieldElement>,
+    position: usize,
+    pending: Vec<u8>,
+}
+
+impl PoseidonSponge {
+
+    pub fn new() -> PoseidonSponge {
+        PoseidonSponge{state: vec![FieldElement::zero(); default_poseidon().width], position: 0, pending: vec![]}
+    }
+
+    fn rate(&self) -> usize {
+        self.state.len() - 1
+    }
+
+    pub fn absorb_elements(&mut self, elements: &[FieldElement]) {
+
+        assert!(self.pending.is_empty(), "bytes must be squeezed before absorbing elements");
+        for e in elements.iter() {
+            self.state[self.position] = self.state[self.position] + *e;
+            self.position += 1;
+
+            if self.position == self.rate() {
+                default_poseidon().permute(&mut self.state);
+                self.position = 0;
+            }
+        }
+    }
+
+    // closes absorbed bytes with a 0x01 marker, which gets an element of its own after a full chunk
+    fn flush_bytes(&mut self) {
+        let mut last = std::mem::take(&mut self.pending);
+        last.push(0x01);
+        self.absorb_elements(&[pack(&last)]);
+    }
+
+    // pads with a one, permutes and outputs the rate, absorbing continues afterwards
+    pub fn squeeze_elements(&mut self) -> Vec<FieldElement> {
+
+        self.state[self.position] = self.state[self.position] + FieldElement::one();
+        default_poseidon().permute(&mut self.state);
+        self.position = 0;
+
+        self.state[..self.rate()].to_vec()
+    }
+}
+
+impl Default for PoseidonSponge {
+    fn default() -> PoseidonSponge {
+        PoseidonSponge::new()
+    }
+}
+
+// little-endian value of at most 15 bytes
+fn pack(bytes: &[u8]) -> FieldElement {
+    let mut word = [0u8; 16];
+    word[..bytes.len()].copy_from_slice(bytes);
+    FieldElement::new(u128::from_le_bytes(word))
+}
+
+impl Sponge for PoseidonSponge {
+
+    fn absorb(&mut self, bytes: &[u8]) {
+
+        for b in bytes.iter() {
+            self.pending.push(*b);
+
+            if self.pending.len() == BYTES_PER_ELEMENT {
+                let element = pack(&self.pending);
+                self.pending.clear();
+                self.absorb_elements(&[element]);
+            }
+        }
+    }
+
+    // the two rate registers as 32 bytes
+    fn squeeze(&mut self) -> [u8; 32] {
+
+        self.flush_bytes();
+        let out = self.squeeze_elements();
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&out[0].to_le_bytes());
+        bytes[16..].copy_from_slice(&out[1].to_le_bytes());
+        bytes
+    }
+}
+
+impl Digest for FieldElement {
+
+    fn to_bytes(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+// Poseidon as a Merkle and Fiat-Shamir hash with field element digests; a digest is a single
+// element and the capacity is one element, so collision resistance is only about 64 bits
+// (birthday bound on a ~128-bit value), use Keccak where 128-bit security is needed
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoseidonHasher;
+
+impl Hasher for PoseidonHasher {
+
+    type Digest = FieldElement;
+
+    type Sponge = PoseidonSponge;
+
+    const ID: u8 = 2;
+
+    fn hash(bytes: &[u8]) -> FieldElement {
+        let mut sponge = PoseidonSponge::new();
+        sponge.absorb(bytes);
+        sponge.flush_bytes();
+        sponge.squeeze_elements()[0]
+    }
+
+    // rows over the native field (or extensions of it) are absorbed element by element,
+    // rows over other fields fall back to hashing their encoding
+    fn hash_elements<F: Field>(elements: &[F]) -> FieldElement {
+        let native: Option<Vec<Vec<FieldElement>>> = elements.iter().map(|e| e.prime_coordinates()).collect();
+        match native {
+            Some(native) => {
+                let mut sponge = PoseidonSponge::new();
+                sponge.absorb_elements(&native.concat());
+                sponge.squeeze_elements()[0]
+            }
+            None => {
+                let bytes: Vec<u8> = elements.iter().flat_map(|e| e.to_bytes()).collect();
+                Self::hash(&bytes)
+            }
+        }
+    }
+
+    fn two_to_one(left: &FieldElement, right: &FieldElement) -> FieldElement {
+        let mut state = vec![*left, *right, FieldElement::zero()];
+        default_poseidon().permute(&mut state);
+        state[0]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::random_element;
+    use crate::extension::QuadraticExtension;
+    use crate::goldilocks::Goldilocks;
+    use crate::fri::Fri;
+    use crate::ip::{ProofError, ProofStream};
+    use crate::merkle::{self, MerkleTree};
+    use crate::univariate::Polynomial;
+
+    #[test]
+    fn test_poseidon_permutation() {
+
+        let poseidon = Poseidon::new(3, 8, 84);
+
+        // cauchy matrix is invertible and constants are deterministic
+        let m = &poseidon.mds;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0]) + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        assert!(!det.is_zero(), "mds matrix is singular");
+        assert!(poseidon.round_constants == Poseidon::new(3, 8, 84).round_constants);
+        assert!(poseidon.round_constants != Poseidon::new(3, 8, 56).round_constants, "constants do not depend on the parameters");
+
+        // different inputs give different outputs, a single changed register diffuses
+        let x: FieldElement = random_element();
+        let mut a = vec![x, FieldElement::zero(), FieldElement::zero()];
+        let mut b = vec![x + FieldElement::one(), FieldElement::zero(), FieldElement::zero()];
+        poseidon.permute(&mut a);
+        poseidon.permute(&mut b);
+        assert!((0..3).all(|i| a[i] != b[i]), "change does not reach every register");
+
+        // other widths work too
+        let wide = Poseidon::new(5, 8, 60);
+        let mut state = vec![FieldElement::one(); 5];
+        wide.permute(&mut state);
+        assert!(state != vec![FieldElement::one(); 5]);
+    }
+
+    #[test]
+    fn test_poseidon_sponge() {
+
+        // chunking does not matter, squeezing continues the transcript
+        let mut first = PoseidonSponge::new();
+        first.absorb(b"poseidon transcript");
+        let mut second = PoseidonSponge::new();
+        second.absorb(b"poseidon ");
+        second.absorb(b"transcript");
+        let challenge = first.squeeze();
+        assert!(challenge == second.squeeze(), "chunked absorb differs");
+        assert!(challenge != first.squeeze(), "sponge repeats a challenge");
+
+        // trailing zero bytes are not lost in the packing
+        assert!(PoseidonHasher::hash(b"a") != PoseidonHasher::hash(b"a\0"), "padding is not injective");
+        assert!(PoseidonHasher::hash(&[0u8; 15]) != PoseidonHasher::hash(&[0u8; 14]), "padding is not injective");
+        assert!(PoseidonHasher::hash(&[[0u8; 14].as_slice(), &[1]].concat()) != PoseidonHasher::hash(&[0u8; 14]), "marker byte collides with padding");
+
+        let row: Vec<FieldElement> = (0..5).map(|_| random_element()).collect();
+        assert!(PoseidonHasher::hash_elements(&row) != PoseidonHasher::hash_elements(&row[..4]));
+
+        // native rows are absorbed as elements, extension rows as their coordinates
+        let mut sponge = PoseidonSponge::new();
+        sponge.absorb_elements(&row);
+        assert!(PoseidonHasher::hash_elements(&row) == sponge.squeeze_elements()[0], "native row is not absorbed as elements");
+        let lifted: Vec<QuadraticExtension<FieldElement>> = row[..4].chunks(2).map(|c| QuadraticExtension::new(c[0], c[1])).collect();
+        assert!(PoseidonHasher::hash_elements(&lifted) == PoseidonHasher::hash_elements(&row[..4]), "extension row is not absorbed by coordinates");
+
+        // rows over another prime are hashed through their encoding
+        let goldilocks: Vec<Goldilocks> = (0..5).map(|_| random_element()).collect();
+        let bytes: Vec<u8> = goldilocks.iter().flat_map(|e| e.to_bytes()).collect();
+        assert!(PoseidonHasher::hash_elements(&goldilocks) == PoseidonHasher::hash(&bytes), "foreign row is not the hash of the encodings");
+    }
+
+    #[test]
+    fn test_poseidon_merkle_fri() {
+
+        let leafs: Vec<FieldElement> = (0..16).map(|_| random_element()).collect();
+        let tree = MerkleTree::<PoseidonHasher>::build(&leafs);
+        for (i, leaf) in leafs.iter().enumerate() {
+            assert!(merkle::verify::<PoseidonHasher, _>(tree.root(), i, &tree.open(i), *leaf), "poseidon merkle path fails");
+            assert!(!merkle::verify::<PoseidonHasher, _>(tree.root(), i, &tree.open(i), *leaf + FieldElement::one()), "poseidon merkle path accepts wrong leaf");
+        }
+
+        // fri with poseidon commitments and challenges
+        let degree = 63;
+        let expansion_factor = 4;
+        let initial_codeword_length = ((degree + 1) * expansion_factor) as u128;
+        let omega = FieldElement::generator().primitive_nth_root(initial_codeword_length);
+        let fri: Fri<FieldElement, FieldElement, PoseidonHasher> = Fri::new(FieldElement::generator(), omega, initial_codeword_length, expansion_factor as u128, 17);
+
+        let polynomial = Polynomial::new((0..degree + 1).map(|i| FieldElement::new(i as u128)).collect());
+        let mut codeword = polynomial.evaluate_domain(&fri.eval_domain());
+
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword.clone(), &mut proof_stream);
+        let proof = proof_stream.serial();
+
+        let mut verifier_stream: ProofStream<FieldElement, PoseidonHasher> = ProofStream::deserial(&proof).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_ok(), "poseidon fri proof is rejected");
+        assert!(ProofStream::<FieldElement>::deserial(&proof).err() == Some(ProofError::HashMismatch), "keccak reader accepts a poseidon proof");
+
+        for i in (0..codeword.len()).step_by(4) {
+            codeword[i] = FieldElement::zero();
+        }
+        let mut proof_stream = ProofStream::new();
+        fri.prove(&mut codeword, &mut proof_stream);
+        let mut verifier_stream: ProofStream<FieldElement, PoseidonHasher> = ProofStream::deserial(&proof_stream.serial()).unwrap();
+        assert!(fri.verify(&mut verifier_stream).is_err(), "poseidon fri accepts a far codeword");
+    }
+}
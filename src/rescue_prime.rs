@@ -0,0 +1,212 @@
+use crate::algebra::{FieldElement, P};
+use crate::keccak::shake256;
+use crate::multivariate::MPolynomial;
+use crate::univariate::Polynomial;
+
+// Rescue-Prime over the 128-bit field, parameters as in the Anatomy of a STARK tutorial
+#[derive(Debug, Clone)]
+pub struct RescuePrime {
+    pub m: usize,
+    pub capacity: usize,
+    pub num_rounds: usize,
+    pub alpha: u128,
+    pub alpha_inv: u128,
+    mds: Vec<Vec<FieldElement>>,
+    mds_inv: Vec<Vec<FieldElement>>,
+    round_constants: Vec<FieldElement>,
+}
+
+impl RescuePrime {
+
+    pub fn new() -> RescuePrime {
+
+        let m = 2;
+        let capacity = 1;
+        let num_rounds = 27;
+        let security_level = 128;
+
+        // -3, 4 / -12, 13 has nonzero entries and determinant 9, so it is MDS
+        let mds = vec![vec![-FieldElement::new(3), FieldElement::new(4)],
+                       vec![-FieldElement::new(12), FieldElement::new(13)]];
+        let det_inv = (mds[0][0] * mds[1][1] - mds[0][1] * mds[1][0]).inverse();
+        let mds_inv = vec![vec![mds[1][1] * det_inv, -mds[0][1] * det_inv],
+                           vec![-mds[1][0] * det_inv, mds[0][0] * det_inv]];
+
+        RescuePrime{m, capacity, num_rounds,
+            // 3 is the smallest exponent coprime to p - 1, alpha_inv is its inverse mod p - 1
+            alpha: 3, alpha_inv: 180331931428153586757283157844700080811,
+            mds, mds_inv,
+            round_constants: RescuePrime::round_constants(m, capacity, security_level, num_rounds)}
+    }
+
+    // constants from SHAKE256 as in the Rescue-Prime specification, 17 little-endian bytes per element
+    fn round_constants(m: usize, capacity: usize, security_level: usize, num_rounds: usize) -> Vec<FieldElement> {
+
+        let bytes_per_int = 17;
+        let seed = format!("Rescue-XLIX({},{},{},{})", P, m, capacity, security_level);
+        let bytes = shake256(seed.as_bytes(), bytes_per_int * 2 * m * num_rounds);
+
+        let shift = FieldElement::new(2) ^ 128;
+        bytes.chunks(bytes_per_int).map(|chunk| {
+            let mut low = [0u8; 16];
+            low.copy_from_slice(&chunk[..16]);
+            FieldElement::new(u128::from_le_bytes(low)) + FieldElement::new(chunk[16] as u128) * shift
+        }).collect()
+    }
+
+    fn mds_multiply(&self, state: &[FieldElement], matrix: &[Vec<FieldElement>]) -> Vec<FieldElement> {
+
+        (0..self.m).map(|i| {
+            let mut acc = FieldElement::zero();
+            for j in 0..self.m {
+                acc = acc + matrix[i][j] * state[j];
+            }
+            acc
+        }).collect()
+    }
+
+    fn round(&self, state: &[FieldElement], r: usize) -> Vec<FieldElement> {
+
+        // forward half-round: S-box, matrix, constants
+        let state: Vec<FieldElement> = state.iter().map(|s| *s ^ self.alpha).collect();
+        let state = self.mds_multiply(&state, &self.mds);
+        let state: Vec<FieldElement> = (0..self.m).map(|i| state[i] + self.round_constants[2 * r * self.m + i]).collect();
+
+        // backward half-round: inverse S-box, matrix, constants
+        let state: Vec<FieldElement> = state.iter().map(|s| *s ^ self.alpha_inv).collect();
+        let state = self.mds_multiply(&state, &self.mds);
+        (0..self.m).map(|i| state[i] + self.round_constants[2 * r * self.m + self.m + i]).collect()
+    }
+
+    pub fn hash(&self, input: FieldElement) -> FieldElement {
+        self.trace(input)[self.num_rounds][0]
+    }
+
+    // states before the first and after every round, num_rounds + 1 rows of m registers
+    pub fn trace(&self, input: FieldElement) -> Vec<Vec<FieldElement>> {
+
+        let mut state = vec![FieldElement::zero(); self.m];
+        state[0] = input;
+
+        let mut trace = vec![state.clone()];
+        for r in 0..self.num_rounds {
+            state = self.round(&state, r);
+            trace.push(state.clone());
+        }
+        trace
+    }
+
+    // capacity starts at zero and the rate holds the output after the last round
+    pub fn boundary_constraints(&self, output: FieldElement) -> Vec<(usize, usize, FieldElement)> {
+        let mut constraints: Vec<(usize, usize, FieldElement)> = (self.m - self.capacity..self.m).map(|i| (0, i, FieldElement::zero())).collect();
+        constraints.push((self.num_rounds, 0, output));
+        constraints
+    }
+
+    // round constants as polynomials in the cycle variable, taking constant r at omicron^r
+    fn round_constants_polynomials(&self, omicron: FieldElement) -> (Vec<MPolynomial>, Vec<MPolynomial>) {
+
+        let domain: Vec<FieldElement> = (0..self.num_rounds).map(|r| omicron ^ r as u128).collect();
+
+        let mut first_step_constants = vec![];
+        let mut second_step_constants = vec![];
+        for i in 0..self.m {
+            let values: Vec<FieldElement> = (0..self.num_rounds).map(|r| self.round_constants[2 * r * self.m + i]).collect();
+            first_step_constants.push(MPolynomial::lift(Polynomial::interpolate_domain(&domain, &values), 0));
+
+            let values: Vec<FieldElement> = (0..self.num_rounds).map(|r| self.round_constants[2 * r * self.m + self.m + i]).collect();
+            second_step_constants.push(MPolynomial::lift(Polynomial::interpolate_domain(&domain, &values), 0));
+        }
+        (first_step_constants, second_step_constants)
+    }
+
+    // one round over variables (cycle, previous state, next state), meeting in the middle
+    // so that only the forward S-box is raised: MDS * prev^alpha + c1 = (MDS^-1 * (next - c2))^alpha
+    pub fn transition_constraints(&self, omicron: FieldElement) -> Vec<MPolynomial> {
+
+        let (first_step_constants, second_step_constants) = self.round_constants_polynomials(omicron);
+
+        let variables = MPolynomial::variables(1 + 2 * self.m);
+        let previous_state = &variables[1..1 + self.m];
+        let next_state = &variables[1 + self.m..1 + 2 * self.m];
+
+        let mut air = vec![];
+        for (i, first_step_constant) in first_step_constants.iter().enumerate() {
+            let mut lhs = MPolynomial::zero();
+            for (entry, previous) in self.mds[i].iter().zip(previous_state.iter()) {
+                lhs = lhs + MPolynomial::constant(*entry) * (previous.clone() ^ self.alpha);
+            }
+            lhs = lhs + first_step_constant.clone();
+
+            let mut rhs = MPolynomial::zero();
+            for ((entry, next), constant) in self.mds_inv[i].iter().zip(next_state.iter()).zip(second_step_constants.iter()) {
+                rhs = rhs + MPolynomial::constant(*entry) * (next.clone() - constant.clone());
+            }
+            rhs = rhs ^ self.alpha;
+
+            air.push(lhs - rhs);
+        }
+        air
+    }
+}
+
+impl Default for RescuePrime {
+    fn default() -> RescuePrime {
+        RescuePrime::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::random_element;
+
+    #[test]
+    fn test_rescue_prime() {
+
+        let rp = RescuePrime::new();
+
+        // vectors from the tutorial
+        assert!(rp.hash(FieldElement::new(1)).value == 244180265933090377212304188905974087294, "hash of 1 is wrong");
+        assert!(rp.hash(FieldElement::new(57322816861100832358702415967512842988)).value == 89633745865384635541695204788332415101, "hash of second vector is wrong");
+
+        let x: FieldElement = random_element();
+        assert!((x ^ rp.alpha) ^ rp.alpha_inv == x, "alpha_inv does not invert the S-box");
+
+        // trace starts from the input with zero capacity and ends in the hash
+        let trace = rp.trace(x);
+        assert!(trace.len() == rp.num_rounds + 1);
+        assert!(trace[0] == vec![x, FieldElement::zero()]);
+        for (cycle, register, value) in rp.boundary_constraints(rp.hash(x)) {
+            assert!(trace[cycle][register] == value, "boundary constraint does not hold on the trace");
+        }
+    }
+
+    #[test]
+    fn test_rescue_prime_constraints() {
+
+        let rp = RescuePrime::new();
+        let omicron = FieldElement::generator().primitive_nth_root(32);
+        let air = rp.transition_constraints(omicron);
+
+        let trace = rp.trace(random_element::<FieldElement>());
+        for r in 0..rp.num_rounds {
+            let mut point = vec![omicron ^ r as u128];
+            point.extend(trace[r].iter().cloned());
+            point.extend(trace[r + 1].iter().cloned());
+            assert!(air.iter().all(|a| a.evaluate(&point).is_zero()), "transition constraint fails in round {}", r);
+        }
+
+        // a wrong next state or a wrong round breaks the constraints
+        let mut point = vec![omicron];
+        point.extend(trace[1].iter().cloned());
+        point.extend(trace[1].iter().cloned());
+        assert!(air.iter().any(|a| !a.evaluate(&point).is_zero()), "constraints accept a skipped round");
+
+        let mut point = vec![omicron ^ 2];
+        point.extend(trace[0].iter().cloned());
+        point.extend(trace[1].iter().cloned());
+        assert!(air.iter().any(|a| !a.evaluate(&point).is_zero()), "constraints accept the wrong round constants");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::algebra::{random_element, FieldElement};
+use crate::ip::VerifyError;
+use crate::multivariate::MPolynomial;
+use crate::rescue_prime::RescuePrime;
+use crate::stark::Stark;
+
+// Rescue-Prime STARK signature scheme: the secret key is a preimage, the public key its hash,
+// and a signature is a STARK proof of knowledge of the preimage with the message bound into Fiat-Shamir
+pub struct Rpsss {
+    rp: RescuePrime,
+    stark: Stark,
+    transition_constraints: Vec<MPolynomial>,
+}
+
+impl Rpsss {
+
+    pub fn new() -> Rpsss {
+        Rpsss::with_parameters(4, 64)
+    }
+
+    // security level is twice the number of colinearity checks
+    pub fn with_parameters(expansion_factor: usize, num_colinearity_checks: usize) -> Rpsss {
+
+        let rp = RescuePrime::new();
+        let stark: Stark = Stark::new(expansion_factor, num_colinearity_checks, 2 * num_colinearity_checks, rp.m, rp.num_rounds + 1, rp.alpha as usize);
+        let transition_constraints = rp.transition_constraints(stark.omicron());
+
+        Rpsss{rp, stark, transition_constraints}
+    }
+
+    pub fn keygen(&self) -> (FieldElement, FieldElement) {
+
+        let sk: FieldElement = random_element();
+
+        (sk, self.rp.hash(sk))
+    }
+
+    // the public key is bound as well, so a signature cannot be replayed under another key
+    fn prefix(pk: FieldElement, message: &[u8]) -> Vec<u8> {
+        [&pk.to_le_bytes()[..], message].concat()
+    }
+
+    // the signature is a STARK proof in the crate's proof format
+    pub fn sign(&self, sk: FieldElement, message: &[u8]) -> Vec<u8> {
+
+        let pk = self.rp.hash(sk);
+        let trace = self.rp.trace(sk);
+        let boundary = self.rp.boundary_constraints(pk);
+
+        self.stark.prove_with_prefix(&trace, &self.transition_constraints, &boundary, &Rpsss::prefix(pk, message))
+    }
+
+    pub fn verify(&self, pk: FieldElement, message: &[u8], signature: &[u8]) -> Result<(), VerifyError> {
+
+        let boundary = self.rp.boundary_constraints(pk);
+        self.stark.verify_with_prefix(signature, &self.transition_constraints, &boundary, &Rpsss::prefix(pk, message))
+    }
+}
+
+impl Default for Rpsss {
+    fn default() -> Rpsss {
+        Rpsss::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ip::{ProofError, ProofReader, FORMAT_VERSION};
+
+    #[test]
+    fn test_rpsss() {
+
+        let rpsss = Rpsss::with_parameters(4, 16);
+        let (sk, pk) = rpsss.keygen();
+
+        let message = b"Do not sign anything you do not understand.";
+        let signature = rpsss.sign(sk, message);
+        assert!(rpsss.verify(pk, message, &signature) == Ok(()), "valid signature is rejected");
+
+        // signatures are proofs in the crate format
+        let reader: ProofReader = ProofReader::read(&signature).unwrap();
+        assert!(reader.version == FORMAT_VERSION);
+
+        // forged message
+        assert!(rpsss.verify(pk, b"Do not sign anything you do not understand!", &signature).is_err(), "signature verifies for another message");
+        assert!(rpsss.verify(pk, b"", &signature).is_err(), "signature verifies for the empty message");
+
+        // wrong key
+        let (_, other_pk) = rpsss.keygen();
+        assert!(rpsss.verify(other_pk, message, &signature).is_err(), "signature verifies under another key");
+
+        // a signature under another key does not verify for this one
+        let (other_sk, _) = rpsss.keygen();
+        assert!(rpsss.verify(pk, message, &rpsss.sign(other_sk, message)).is_err(), "signature by another key verifies");
+
+        // tampered and malformed signatures
+        let mut tampered = signature.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(rpsss.verify(pk, message, &tampered).is_err(), "tampered signature verifies");
+        assert!(rpsss.verify(pk, message, &[0xff_u8; 64]) == Err(VerifyError::Proof(ProofError::BadMagic)), "garbage signature is accepted");
+
+        // signatures are bound to the scheme parameters
+        let other = Rpsss::with_parameters(4, 17);
+        assert!(other.verify(pk, message, &signature) == Err(VerifyError::Proof(ProofError::ParameterMismatch)), "signature verifies under other parameters");
+    }
+}
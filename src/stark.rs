@@ -0,0 +1,583 @@
+use std::cmp;
+use std::collections::HashMap;
+use crate::algebra::{random_element, Field, FieldElement};
+use crate::fri::Fri;
+use crate::ip::{Object, ProofError, ProofReader, ProofStream, ProofWriter, VerifyError, FORMAT_VERSION};
+use crate::hasher::{Hasher, Keccak};
+use crate::merkle::{self, MerkleTree, RowTree};
+use crate::multivariate::MPolynomial;
+use crate::univariate::Polynomial;
+
+// H commits to the trace and derives every Fiat-Shamir challenge
+#[derive(Debug)]
+pub struct Stark<H: Hasher = Keccak> {
+    expansion_factor: usize,
+    num_colinearity_checks: usize,
+    security_level: usize,
+    num_randomizers: usize,
+    num_registers: usize,
+    original_trace_length: usize,
+    generator: FieldElement,
+    omega: FieldElement,
+    omicron: FieldElement,
+    omicron_domain: Vec<FieldElement>,
+    fri_domain_length: usize,
+    fri: Fri<FieldElement, FieldElement, H>,
+}
+
+impl<H: Hasher> Stark<H> {
+
+    pub fn new(expansion_factor: usize, num_colinearity_checks: usize, security_level: usize,
+            num_registers: usize, num_cycles: usize, transition_constraints_degree: usize) -> Self {
+
+        assert!(FieldElement::BITS as usize >= security_level, "p must have at least as many bits as security level");
+        assert!(expansion_factor & (expansion_factor - 1) == 0, "expansion factor must be a power of 2");
+        assert!(expansion_factor >= 4, "expansion factor must be 4 or greater");
+        assert!(num_colinearity_checks * 2 >= security_level, "number of colinearity checks must be at least half of security level");
+
+        // at least 4 randomizers per colinearity check, padded so the randomized trace fills
+        // the subgroup generated by omicron and interpolates through the ntt
+        let randomized_trace_length = (num_cycles + 4 * num_colinearity_checks).next_power_of_two();
+        let num_randomizers = randomized_trace_length - num_cycles;
+
+        let omicron_domain_length = randomized_trace_length;
+        let fri_domain_length = (1 << (usize::BITS - (randomized_trace_length * transition_constraints_degree).leading_zeros())) * expansion_factor;
+
+        let generator = FieldElement::generator();
+        let omega = generator.primitive_nth_root(fri_domain_length as u128);
+        let omicron = generator.primitive_nth_root(omicron_domain_length as u128);
+
+        let mut omicron_domain: Vec<FieldElement> = vec![];
+        for i in 0..omicron_domain_length {
+            omicron_domain.push(omicron ^ i as u128);
+        }
+
+        let fri = Fri::new(generator, omega, fri_domain_length as u128, expansion_factor as u128, num_colinearity_checks as u128);
+
+        Stark{expansion_factor, num_colinearity_checks,
+            security_level, num_randomizers, num_registers,
+            original_trace_length: num_cycles, generator, omega, omicron,
+            omicron_domain, fri_domain_length, fri}
+    }
+
+    // parameters recorded in the proof header, so a proof cannot be checked against another configuration
+    fn parameters(&self) -> Vec<u8> {
+        let values = [self.expansion_factor, self.num_colinearity_checks, self.security_level,
+            self.num_registers, self.original_trace_length, self.num_randomizers];
+
+        values.iter().flat_map(|v| (*v as u64).to_le_bytes()).collect()
+    }
+
+    // what the proof claims, bound into the transcript before the first object
+    fn statement(&self, boundary: &[(usize, usize, FieldElement)]) -> Vec<u8> {
+        let mut bytes = self.parameters();
+        for (cycle, register, value) in boundary.iter() {
+            bytes.extend((*cycle as u64).to_le_bytes());
+            bytes.extend((*register as u64).to_le_bytes());
+            bytes.extend(value.to_bytes());
+        }
+        bytes
+    }
+
+    // every boundary constraint must name a cycle of the original trace and one of its registers
+    fn boundary_in_range(&self, boundary: &[(usize, usize, FieldElement)]) -> bool {
+        boundary.iter().all(|(c, r, _)| *c < self.original_trace_length && *r < self.num_registers)
+    }
+
+    fn transition_degree_bounds(&self, transition_constraints: &[MPolynomial]) -> Vec<i128> {
+
+        // x has degree 1, every register (current and next) has the degree of a trace polynomial
+        let mut point_degrees: Vec<i128> = vec![1];
+        for _ in 0..2 * self.num_registers {
+            point_degrees.push((self.original_trace_length + self.num_randomizers - 1) as i128);
+        }
+
+        let mut bounds: Vec<i128> = vec![];
+        for a in transition_constraints.iter() {
+            let mut max = 0;
+            // zero coefficients do not raise the degree
+            for (k, _) in a.dictionary.iter().filter(|(_, v)| !v.is_zero()) {
+                let mut degree = 0;
+                for (r, l) in point_degrees.iter().zip(k.iter()) {
+                    degree += r * (*l as i128);
+                }
+                max = cmp::max(max, degree);
+            }
+            bounds.push(max);
+        }
+        bounds
+    }
+
+    fn transition_quotient_degree_bounds(&self, transition_constraints: &[MPolynomial]) -> Vec<i128> {
+        self.transition_degree_bounds(transition_constraints).iter().map(|d| d - (self.original_trace_length as i128 - 1)).collect()
+    }
+
+    fn max_degree(&self, transition_constraints: &[MPolynomial]) -> i128 {
+        let md = self.transition_quotient_degree_bounds(transition_constraints).into_iter().max().unwrap();
+        (1 << (128 - md.leading_zeros())) - 1
+    }
+
+    // omega ^ omicron_step == omicron, so omicron * x lies omicron_step indices after x in the FRI domain
+    fn omicron_step(&self) -> usize {
+        self.fri_domain_length / self.omicron_domain.len()
+    }
+
+    fn transition_zerofier(&self) -> Polynomial {
+        Polynomial::zerofier_domain(&self.omicron_domain[..self.original_trace_length - 1])
+    }
+
+    fn boundary_zerofiers(&self, boundary: &[(usize, usize, FieldElement)]) -> Vec<Polynomial> {
+        let mut zerofiers: Vec<Polynomial> = vec![];
+        for s in 0..self.num_registers {
+            let points: Vec<FieldElement> = boundary.iter().filter(|(_, r, _)| *r == s).map(|(c, _, _)| self.omicron ^ *c as u128).collect();
+            zerofiers.push(Polynomial::zerofier_domain(&points));
+        }
+        zerofiers
+    }
+
+    fn boundary_interpolants(&self, boundary: &[(usize, usize, FieldElement)]) -> Vec<Polynomial> {
+        let mut interpolants: Vec<Polynomial> = vec![];
+        for s in 0..self.num_registers {
+            let domain: Vec<FieldElement> = boundary.iter().filter(|(_, r, _)| *r == s).map(|(c, _, _)| self.omicron ^ *c as u128).collect();
+            let values: Vec<FieldElement> = boundary.iter().filter(|(_, r, _)| *r == s).map(|(_, _, v)| *v).collect();
+
+            if domain.is_empty() {
+                interpolants.push(Polynomial::new(vec![]));
+            } else {
+                interpolants.push(Polynomial::interpolate_domain(&domain, &values));
+            }
+        }
+        interpolants
+    }
+
+    fn boundary_quotient_degree_bounds(&self, randomized_trace_length: usize, boundary: &[(usize, usize, FieldElement)]) -> Vec<i128> {
+        let randomized_trace_degree = randomized_trace_length as i128 - 1;
+        self.boundary_zerofiers(boundary).iter().map(|bz| randomized_trace_degree - bz.degree()).collect()
+    }
+
+
+    pub fn omicron(&self) -> FieldElement {
+        self.omicron
+    }
+
+    pub fn prove(&self, trace: &[Vec<FieldElement>], transition_constraints: &[MPolynomial],
+            boundary: &[(usize, usize, FieldElement)]) -> Vec<u8> {
+        self.prove_with_prefix(trace, transition_constraints, boundary, &[])
+    }
+
+    // the prefix is bound into the Fiat-Shamir challenges but not stored in the proof
+    pub fn prove_with_prefix(&self, trace: &[Vec<FieldElement>], transition_constraints: &[MPolynomial],
+            boundary: &[(usize, usize, FieldElement)], prefix: &[u8]) -> Vec<u8> {
+
+        assert!(trace.len() == self.original_trace_length, "trace length does not match number of cycles");
+        assert!(self.boundary_in_range(boundary), "boundary constraint outside the trace");
+
+        let mut proof_stream: ProofStream<FieldElement, H> = ProofStream::new();
+        proof_stream.bind_prefix(&self.statement(boundary));
+        // bound even when empty, so the statement is always followed by a length
+        proof_stream.bind_prefix(prefix);
+
+        // concatenate randomizers
+        let mut trace = trace.to_vec();
+        for _ in 0..self.num_randomizers {
+            let mut row: Vec<FieldElement> = vec![];
+            for _ in 0..self.num_registers {
+                row.push(random_element());
+            }
+            trace.push(row);
+        }
+
+        // interpolate
+        let trace_domain: Vec<FieldElement> = (0..trace.len()).map(|i| self.omicron ^ i as u128).collect();
+        let mut trace_polynomials: Vec<Polynomial> = vec![];
+        for s in 0..self.num_registers {
+            let single_trace: Vec<FieldElement> = trace.iter().map(|row| row[s]).collect();
+            trace_polynomials.push(Polynomial::interpolate_domain(&trace_domain, &single_trace));
+        }
+
+        // subtract boundary interpolants and divide out boundary zerofiers
+        let boundary_interpolants = self.boundary_interpolants(boundary);
+        let boundary_zerofiers = self.boundary_zerofiers(boundary);
+        let mut boundary_quotients: Vec<Polynomial> = vec![];
+        for s in 0..self.num_registers {
+            let quotient = (trace_polynomials[s].clone() - boundary_interpolants[s].clone()) / boundary_zerofiers[s].clone();
+            boundary_quotients.push(quotient);
+        }
+
+        // commit to boundary quotients, one leaf holds every register at a point
+        let fri_domain = self.fri.eval_domain();
+        let mut boundary_quotient_codewords: Vec<Vec<FieldElement>> = vec![];
+        for quotient in boundary_quotients.iter() {
+            boundary_quotient_codewords.push(quotient.evaluate_domain(&fri_domain));
+        }
+        let boundary_quotient_tree: RowTree<FieldElement, H> = RowTree::from_columns(&boundary_quotient_codewords);
+        proof_stream.push(&Object::MerkleRoot(boundary_quotient_tree.root()));
+
+        // symbolically evaluate transition constraints
+        let x = Polynomial::new(vec![FieldElement::zero(), FieldElement::one()]);
+        let mut point: Vec<Polynomial> = vec![x.clone()];
+        point.extend(trace_polynomials.iter().cloned());
+        point.extend(trace_polynomials.iter().map(|tp| tp.scale(self.omicron)));
+
+        let transition_polynomials: Vec<Polynomial> = transition_constraints.iter().map(|a| a.evaluate_symbolic(&point)).collect();
+
+        // divide out zerofier
+        let transition_zerofier = self.transition_zerofier();
+        let transition_quotients: Vec<Polynomial> = transition_polynomials.into_iter().map(|tp| tp / transition_zerofier.clone()).collect();
+
+        // commit to randomizer polynomial
+        let max_degree = self.max_degree(transition_constraints);
+        let randomizer_polynomial = Polynomial::new((0..max_degree + 1).map(|_| random_element()).collect());
+        let randomizer_codeword = randomizer_polynomial.evaluate_domain(&fri_domain);
+        let randomizer_tree: MerkleTree<H> = MerkleTree::build(&randomizer_codeword);
+        proof_stream.push(&Object::MerkleRoot(randomizer_tree.root()));
+
+        // get weights for nonlinear combination
+        //  - 1 randomizer
+        //  - 2 for every transition quotient
+        //  - 2 for every boundary quotient
+        let weights: Vec<FieldElement> = (0..1 + 2 * transition_quotients.len() + 2 * boundary_quotients.len()).map(|_| proof_stream.prover_sample()).collect();
+
+        let transition_quotient_degree_bounds = self.transition_quotient_degree_bounds(transition_constraints);
+        let tq_degrees: Vec<i128> = transition_quotients.iter().map(|tq| tq.degree()).collect();
+        assert!(tq_degrees == transition_quotient_degree_bounds, "transition quotient degrees do not match with expectation");
+
+        // compute terms of nonlinear combination polynomial
+        let boundary_quotient_degree_bounds = self.boundary_quotient_degree_bounds(trace.len(), boundary);
+        let mut terms: Vec<Polynomial> = vec![randomizer_polynomial];
+        for i in 0..transition_quotients.len() {
+            terms.push(transition_quotients[i].clone());
+            let shift = max_degree - transition_quotient_degree_bounds[i];
+            terms.push((x.clone() ^ shift as u128) * transition_quotients[i].clone());
+        }
+        for i in 0..self.num_registers {
+            terms.push(boundary_quotients[i].clone());
+            let shift = max_degree - boundary_quotient_degree_bounds[i];
+            terms.push((x.clone() ^ shift as u128) * boundary_quotients[i].clone());
+        }
+
+        // take weighted sum
+        let mut combination = Polynomial::new(vec![]);
+        for i in 0..terms.len() {
+            combination = combination + Polynomial::new(vec![weights[i]]) * terms[i].clone();
+        }
+
+        // compute matching codeword
+        let mut combined_codeword = combination.evaluate_domain(&fri_domain);
+
+        // prove low degree of combination polynomial
+        let mut indices = self.fri.prove(&mut combined_codeword, &mut proof_stream);
+        indices.sort();
+        let mut duplicated_indices = indices.clone();
+        duplicated_indices.extend(indices.iter().map(|i| (i + self.omicron_step()) % self.fri_domain_length));
+
+        // open indicated rows of the boundary quotient codewords
+        let (rows, proof) = boundary_quotient_tree.open_many(&duplicated_indices);
+        for row in rows {
+            proof_stream.push(&Object::Row(row));
+        }
+        proof_stream.push(&Object::MerkleMultiPath(proof));
+
+        // ... as well as in the randomizer
+        for i in indices.iter() {
+            proof_stream.push(&Object::Point(randomizer_codeword[*i]));
+        }
+        proof_stream.push(&Object::MerkleMultiPath(randomizer_tree.open_many(&indices)));
+
+        // the final proof is the serialized stream tagged with the parameters
+        ProofWriter::new(&self.parameters()).write(&proof_stream)
+    }
+
+    pub fn verify(&self, proof: &[u8], transition_constraints: &[MPolynomial],
+            boundary: &[(usize, usize, FieldElement)]) -> Result<(), VerifyError> {
+        self.verify_with_prefix(proof, transition_constraints, boundary, &[])
+    }
+
+    pub fn verify_with_prefix(&self, proof: &[u8], transition_constraints: &[MPolynomial],
+            boundary: &[(usize, usize, FieldElement)], prefix: &[u8]) -> Result<(), VerifyError> {
+
+        let randomized_trace_length = self.original_trace_length + self.num_randomizers;
+
+        if !self.boundary_in_range(boundary) {
+            return Err(VerifyError::InvalidStatement);
+        }
+
+        let reader: ProofReader<FieldElement, H> = ProofReader::read(proof)?;
+        if reader.version != FORMAT_VERSION {
+            return Err(VerifyError::Proof(ProofError::UnsupportedVersion));
+        }
+        if reader.parameters != self.parameters() {
+            return Err(VerifyError::Proof(ProofError::ParameterMismatch));
+        }
+        let mut proof_stream = reader.into_stream();
+        proof_stream.bind_prefix(&self.statement(boundary));
+        // bound even when empty, so the statement is always followed by a length
+        proof_stream.bind_prefix(prefix);
+
+        // get Merkle root of boundary quotient rows
+        let boundary_quotient_root = proof_stream.pull_root()?;
+
+        // get Merkle root of randomizer polynomial
+        let randomizer_root = proof_stream.pull_root()?;
+
+        // get weights for nonlinear combination
+        let weights: Vec<FieldElement> = (0..1 + 2 * transition_constraints.len() + 2 * self.num_registers).map(|_| proof_stream.verifier_sample()).collect();
+
+        // verify low degree of combination polynomial
+        let mut polynomial_values = self.fri.verify(&mut proof_stream)?;
+        polynomial_values.sort_by_key(|iv| iv.0);
+
+        let indices: Vec<usize> = polynomial_values.iter().map(|(i, _)| *i).collect();
+        let values: Vec<FieldElement> = polynomial_values.iter().map(|(_, v)| *v).collect();
+
+        // read and verify leafs, which are elements of boundary quotient codewords
+        let mut duplicated_indices = indices.clone();
+        duplicated_indices.extend(indices.iter().map(|i| (i + self.omicron_step()) % self.fri_domain_length));
+
+        let mut leafs: Vec<HashMap<usize, FieldElement>> = vec![HashMap::new(); self.num_registers];
+        let mut rows: Vec<Vec<FieldElement>> = vec![];
+        for i in duplicated_indices.iter() {
+            let row = proof_stream.pull_row()?;
+            if row.len() != self.num_registers {
+                return Err(VerifyError::BoundaryQuotientPath);
+            }
+            for s in 0..self.num_registers {
+                leafs[s].insert(*i, row[s]);
+            }
+            rows.push(row);
+        }
+        let proof = proof_stream.pull_multi_path()?;
+        if !merkle::verify_rows_many::<H, _>(boundary_quotient_root, self.fri_domain_length, &duplicated_indices, &rows, &proof) {
+            return Err(VerifyError::BoundaryQuotientPath);
+        }
+
+        // read and verify randomizer leafs
+        let mut randomizer: HashMap<usize, FieldElement> = HashMap::new();
+        let mut opened: Vec<FieldElement> = vec![];
+        for i in indices.iter() {
+            let leaf = proof_stream.pull_point()?;
+            opened.push(leaf);
+            randomizer.insert(*i, leaf);
+        }
+        let proof = proof_stream.pull_multi_path()?;
+        if !merkle::verify_many::<H, _>(randomizer_root, self.fri_domain_length, &indices, &opened, &proof) {
+            return Err(VerifyError::RandomizerPath);
+        }
+
+        let boundary_zerofiers = self.boundary_zerofiers(boundary);
+        let boundary_interpolants = self.boundary_interpolants(boundary);
+        let transition_zerofier = self.transition_zerofier();
+        let transition_quotient_degree_bounds = self.transition_quotient_degree_bounds(transition_constraints);
+        let boundary_quotient_degree_bounds = self.boundary_quotient_degree_bounds(randomized_trace_length, boundary);
+        let max_degree = self.max_degree(transition_constraints);
+
+        // transition zerofier values in the queried points, inverted in one batch
+        let zerofier_values: Vec<FieldElement> = indices.iter()
+            .map(|index| transition_zerofier.evaluate(self.generator * (self.omega ^ *index as u128)))
+            .collect();
+        let zerofier_inverses = FieldElement::batch_inverse(&zerofier_values);
+
+        // verify leafs of combination polynomial
+        for i in 0..indices.len() {
+            let current_index = indices[i];
+
+            // get trace values by applying a correction to the boundary quotient values (which are the leafs)
+            let domain_current_index = self.generator * (self.omega ^ current_index as u128);
+            let next_index = (current_index + self.omicron_step()) % self.fri_domain_length;
+            let domain_next_index = self.generator * (self.omega ^ next_index as u128);
+
+            let mut current_trace: Vec<FieldElement> = vec![];
+            let mut next_trace: Vec<FieldElement> = vec![];
+            for s in 0..self.num_registers {
+                let zerofier = &boundary_zerofiers[s];
+                let interpolant = &boundary_interpolants[s];
+
+                current_trace.push(leafs[s][&current_index] * zerofier.evaluate(domain_current_index) + interpolant.evaluate(domain_current_index));
+                next_trace.push(leafs[s][&next_index] * zerofier.evaluate(domain_next_index) + interpolant.evaluate(domain_next_index));
+            }
+
+            let mut point: Vec<FieldElement> = vec![domain_current_index];
+            point.extend(current_trace);
+            point.extend(next_trace);
+
+            // compute nonlinear combination
+            let mut terms: Vec<FieldElement> = vec![randomizer[&current_index]];
+            for s in 0..transition_constraints.len() {
+                let quotient = transition_constraints[s].evaluate(&point) * zerofier_inverses[i];
+                terms.push(quotient);
+                let shift = max_degree - transition_quotient_degree_bounds[s];
+                terms.push(quotient * (domain_current_index ^ shift as u128));
+            }
+            for s in 0..self.num_registers {
+                let bqv = leafs[s][&current_index];
+                terms.push(bqv);
+                let shift = max_degree - boundary_quotient_degree_bounds[s];
+                terms.push(bqv * (domain_current_index ^ shift as u128));
+            }
+
+            let mut combination = FieldElement::zero();
+            for j in 0..terms.len() {
+                combination = combination + terms[j] * weights[j];
+            }
+
+            // verify against combination polynomial value
+            if combination != values[i] {
+                return Err(VerifyError::Combination);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon::PoseidonHasher;
+
+    // two-register fibonacci: (a, b) -> (b, a + b)
+    fn fibonacci_trace(num_cycles: usize) -> Vec<Vec<FieldElement>> {
+        let mut trace = vec![vec![FieldElement::one(), FieldElement::one()]];
+        while trace.len() < num_cycles {
+            let last = trace[trace.len() - 1].clone();
+            trace.push(vec![last[1], last[0] + last[1]]);
+        }
+        trace
+    }
+
+    fn fibonacci_constraints() -> Vec<MPolynomial> {
+        let variables = MPolynomial::variables(5);
+        let (a, b) = (variables[1].clone(), variables[2].clone());
+        let (a_next, b_next) = (variables[3].clone(), variables[4].clone());
+
+        vec![a_next - b.clone(), b_next - a - b]
+    }
+
+    #[test]
+    fn test_stark() {
+
+        let num_cycles = 8;
+        let stark: Stark = Stark::new(4, 2, 4, 2, num_cycles, 2);
+
+        let trace = fibonacci_trace(num_cycles);
+        let transition_constraints = fibonacci_constraints();
+        let boundary = vec![(0, 0, FieldElement::one()), (0, 1, FieldElement::one()), (num_cycles - 1, 1, trace[num_cycles - 1][1])];
+
+        // a valid proof should be accepted
+        let proof = stark.prove(&trace, &transition_constraints, &boundary);
+        assert!(stark.verify(&proof, &transition_constraints, &boundary) == Ok(()), "valid stark proof fails to verify");
+
+        // the same proof should not be accepted for a different boundary
+        let false_boundary = vec![(0, 0, FieldElement::one()), (0, 1, FieldElement::one()), (num_cycles - 1, 1, FieldElement::new(1337))];
+        assert!(stark.verify(&proof, &transition_constraints, &false_boundary).is_err(), "invalid stark proof verifies");
+
+        // challenges depend on the statement, not only on the objects
+        let mut stream: ProofStream = ProofStream::new();
+        stream.bind_prefix(&stark.statement(&boundary));
+        let mut false_stream: ProofStream = ProofStream::new();
+        false_stream.bind_prefix(&stark.statement(&false_boundary));
+        assert!(stream.prover_fiat_shamir() != false_stream.prover_fiat_shamir(), "challenges do not depend on the boundary");
+
+        // a prefix must be given to the verifier exactly as to the prover
+        let prefixed = stark.prove_with_prefix(&trace, &transition_constraints, &boundary, b"prefix");
+        assert!(stark.verify_with_prefix(&prefixed, &transition_constraints, &boundary, b"prefix") == Ok(()), "prefixed proof fails to verify");
+        assert!(stark.verify(&prefixed, &transition_constraints, &boundary).is_err(), "prefixed proof verifies without its prefix");
+
+        // malformed proofs are rejected with an error instead of a panic
+        let garbage = vec![0xff_u8; 100];
+        assert!(stark.verify(&garbage, &transition_constraints, &boundary) == Err(VerifyError::Proof(ProofError::BadMagic)), "garbage proof is accepted");
+
+        let mut short_stream: ProofStream = ProofStream::new();
+        short_stream.push(&Object::MerkleRoot([0_u8; 32]));
+        let short_proof = ProofWriter::new(&stark.parameters()).write(&short_stream);
+        assert!(stark.verify(&short_proof, &transition_constraints, &boundary) == Err(VerifyError::Proof(ProofError::Exhausted)), "short proof is not exhausted");
+
+        // a proof is bound to the parameters it was made with
+        let other: Stark = Stark::new(4, 3, 4, 2, num_cycles, 2);
+        assert!(other.verify(&proof, &transition_constraints, &boundary) == Err(VerifyError::Proof(ProofError::ParameterMismatch)), "proof verifies under other parameters");
+
+        // proofs of older versions are rejected rather than misread
+        let mut previous = proof.clone();
+        previous[4] = FORMAT_VERSION - 1;
+        assert!(stark.verify(&previous, &transition_constraints, &boundary) == Err(VerifyError::Proof(ProofError::UnsupportedVersion)), "older proof verifies");
+
+        // boundary constraints outside the trace are rejected instead of dropped
+        let past_register = [boundary.clone(), vec![(0, 2, FieldElement::one())]].concat();
+        assert!(stark.verify(&proof, &transition_constraints, &past_register) == Err(VerifyError::InvalidStatement), "boundary on a missing register is accepted");
+        let past_cycle = [boundary.clone(), vec![(num_cycles, 0, FieldElement::one())]].concat();
+        assert!(stark.verify(&proof, &transition_constraints, &past_cycle) == Err(VerifyError::InvalidStatement), "boundary past the last cycle is accepted");
+    }
+
+    #[test]
+    fn test_stark_trace_domain() {
+
+        // the randomized trace fills a power of two subgroup, so the prover interpolates with the ntt
+        for num_cycles in [1, 8, 9, 100] {
+            let stark: Stark = Stark::new(4, 2, 4, 2, num_cycles, 2);
+            let randomized_trace_length = stark.original_trace_length + stark.num_randomizers;
+            assert!(randomized_trace_length.is_power_of_two(), "randomized trace length is not a power of two");
+            assert!(stark.num_randomizers >= 4 * stark.num_colinearity_checks, "padding removes randomizers");
+            assert!(stark.omicron_domain.len() == randomized_trace_length, "omicron does not generate the trace domain");
+            assert!(stark.omicron ^ (randomized_trace_length / 2) as u128 == -FieldElement::one(), "omicron is not primitive");
+            assert!(stark.omega ^ stark.omicron_step() as u128 == stark.omicron, "omicron is not a power of omega");
+        }
+    }
+
+    #[test]
+    fn test_stark_cancelled_terms() {
+
+        let num_cycles = 8;
+        let stark: Stark = Stark::new(4, 2, 4, 2, num_cycles, 2);
+
+        let trace = fibonacci_trace(num_cycles);
+        let boundary = vec![(0, 0, FieldElement::one()), (0, 1, FieldElement::one())];
+
+        // a high degree monomial with a zero coefficient does not count towards the degree bound
+        let mut transition_constraints = fibonacci_constraints();
+        let mut dictionary = transition_constraints[1].dictionary.clone();
+        dictionary.insert(vec![0, 3, 3, 0, 0], FieldElement::zero());
+        transition_constraints[1] = MPolynomial::new(dictionary);
+        assert!(stark.transition_degree_bounds(&transition_constraints) == stark.transition_degree_bounds(&fibonacci_constraints()), "zero coefficient raises the degree bound");
+
+        let proof = stark.prove(&trace, &transition_constraints, &boundary);
+        assert!(stark.verify(&proof, &transition_constraints, &boundary) == Ok(()), "proof with a cancelled term fails to verify");
+
+        // terms that cancel through arithmetic are dropped as well
+        let variables = MPolynomial::variables(5);
+        let cubic = variables[1].clone() ^ 3;
+        let cancelling: Vec<MPolynomial> = fibonacci_constraints().into_iter().map(|c| c + cubic.clone() - cubic.clone()).collect();
+        let proof = stark.prove(&trace, &cancelling, &boundary);
+        assert!(stark.verify(&proof, &cancelling, &boundary) == Ok(()), "proof with cancelling constraints fails to verify");
+    }
+
+    #[test]
+    #[should_panic(expected = "boundary constraint outside the trace")]
+    fn test_stark_boundary_out_of_range() {
+
+        let num_cycles = 8;
+        let stark: Stark = Stark::new(4, 2, 4, 2, num_cycles, 2);
+
+        let trace = fibonacci_trace(num_cycles);
+        let boundary = vec![(0, 0, FieldElement::one()), (num_cycles, 1, FieldElement::one())];
+        stark.prove(&trace, &fibonacci_constraints(), &boundary);
+    }
+
+    #[test]
+    fn test_stark_hasher() {
+
+        let num_cycles = 8;
+        let stark: Stark<PoseidonHasher> = Stark::new(4, 2, 4, 2, num_cycles, 2);
+
+        let trace = fibonacci_trace(num_cycles);
+        let transition_constraints = fibonacci_constraints();
+        let boundary = vec![(0, 0, FieldElement::one()), (0, 1, FieldElement::one()), (num_cycles - 1, 1, trace[num_cycles - 1][1])];
+
+        // commitments and weights come from poseidon, so a keccak verifier refuses the proof
+        let proof = stark.prove(&trace, &transition_constraints, &boundary);
+        assert!(stark.verify(&proof, &transition_constraints, &boundary) == Ok(()), "poseidon stark proof fails to verify");
+
+        let keccak_stark: Stark = Stark::new(4, 2, 4, 2, num_cycles, 2);
+        assert!(keccak_stark.verify(&proof, &transition_constraints, &boundary) == Err(VerifyError::Proof(ProofError::HashMismatch)), "keccak verifier accepts a poseidon proof");
+    }
+}
@@ -1,29 +1,30 @@
 use std::cmp;
 use std::ops;
-use crate::algebra::FieldElement;
+use crate::algebra::{Field, FieldElement};
+use crate::ntt;
 
 #[derive(Debug, Clone)]
-pub struct Polynomial {
+pub struct Polynomial<F: Field = FieldElement> {
 
-    pub coefs: Vec<FieldElement>
+    pub coefs: Vec<F>
 
 }
 
-impl Polynomial {
+impl<F: Field> Polynomial<F> {
 
-    fn new (c: Vec<FieldElement>) -> Polynomial {
-        return Polynomial{coefs: c};
+    pub fn new (c: Vec<F>) -> Polynomial<F> {
+        Polynomial{coefs: c}
     }
     
-    fn degree(&self) -> i128 {
-        if self.coefs.len() == 0 {
-            return -1 as i128;
+    pub fn degree(&self) -> i128 {
+        if self.coefs.is_empty() {
+            return -1_i128;
         }
         
-        let zero = FieldElement::zero();
+        let zero = F::zero();
         
         if self.coefs == vec![zero; self.coefs.len()]{
-            return -1 as i128
+            return -1_i128
         }
         
         let mut max = 0;
@@ -33,50 +34,51 @@ impl Polynomial {
             }
         }
         
-        return max as i128;
+        max as i128
     }
     
-    fn is_zero(&self) -> bool {
+    pub fn is_zero(&self) -> bool {
         if self.degree() == -1 {
             return true;
         }
-        return false;
+        false
     }
     
-    fn leading_coefficient(&self) -> FieldElement {
+    fn leading_coefficient(&self) -> F {
         
         if self.degree() == -1 {
-            return FieldElement::zero()
+            return F::zero()
         }
     
-        return self.coefs[self.degree() as usize].clone();
+        self.coefs[self.degree() as usize]
     }
     
     
     
-    fn divide(&self, d: &Polynomial) -> (Polynomial, Polynomial) {
+    fn divide(&self, d: &Polynomial<F>) -> (Polynomial<F>, Polynomial<F>) {
         
         if d.degree() == -1 {
             return (Polynomial::new(vec![]), Polynomial::new(vec![]));
         }
         
         if self.degree() < d.degree(){
-            return (Polynomial::new(vec![]),d.clone());
+            return (Polynomial::new(vec![]),self.clone());
         }
         
         let mut remainder = Polynomial::new(self.coefs.clone());
+        let lc_inverse = d.leading_coefficient().inverse();
         
-        let mut quotient_coefficients = vec![FieldElement::zero(); (self.degree() - d.degree() + 1) as usize];
+        let mut quotient_coefficients = vec![F::zero(); (self.degree() - d.degree() + 1) as usize];
         
-        for i in 0..(self.degree() - d.degree() + 1) as usize {
+        for _ in 0..(self.degree() - d.degree() + 1) as usize {
             if remainder.degree() < d.degree() {
                 break;
             }
             
-            let coefficient = remainder.leading_coefficient() / d.leading_coefficient();
+            let coefficient = remainder.leading_coefficient() * lc_inverse;
             let shift = remainder.degree() - d.degree();
             
-            let mut s  = vec![FieldElement::zero(); shift as usize];
+            let mut s  = vec![F::zero(); shift as usize];
             s.push(coefficient);
             let subtractee = Polynomial::new(s) * d;
             
@@ -84,12 +86,12 @@ impl Polynomial {
             remainder = remainder - subtractee;
         }
         
-        return (Polynomial::new(quotient_coefficients.clone()), remainder);
+        (Polynomial::new(quotient_coefficients.clone()), remainder)
     }
     
-    pub fn evaluate(&self, point: FieldElement) ->  FieldElement {
-        let mut xi = FieldElement::one();
-        let mut value = FieldElement::zero(); 
+    pub fn evaluate(&self, point: F) ->  F {
+        let mut xi = F::one();
+        let mut value = F::zero(); 
         
         for i in 0..self.coefs.len() {
             value = value + self.coefs[i] * xi;
@@ -98,93 +100,152 @@ impl Polynomial {
         value
     }
     
-    pub fn evaluate_domain(&self, domain: &Vec<FieldElement> ) -> Vec<FieldElement> {
-        let mut values: Vec<FieldElement> = vec![];
+    // returns (offset, omega) when domain is offset * omega^i for a primitive omega of order domain.len()
+    fn coset_structure(domain: &[F]) -> Option<(F, F)> {
+        let n = domain.len();
         
-        for i in 0..domain.len() {
-            values.push(self.evaluate(domain[i]));
+        if n < 2 || n & (n - 1) != 0 || domain[0].is_zero() {
+            return None;
+        }
+        
+        let offset = domain[0];
+        let omega = domain[1] / domain[0];
+        
+        for i in 1..n {
+            if domain[i] != domain[i-1] * omega {
+                return None;
+            }
+        }
+        
+        if domain[n-1] * omega != offset || omega ^ (n / 2) as u128 != -F::one() {
+            return None;
+        }
+        
+        Some((offset, omega))
+    }
+    
+    pub fn evaluate_domain(&self, domain: &[F]) -> Vec<F> {
+        
+        if let Some((offset, omega)) = Polynomial::coset_structure(domain) {
+            
+            // reduce modulo x^n - offset^n, which vanishes on the coset
+            let n = domain.len();
+            let offset_n = offset ^ n as u128;
+            let mut folded = vec![F::zero(); n];
+            let mut factor = F::one();
+            
+            for i in 0..self.coefs.len() {
+                if i > 0 && i % n == 0 {
+                    factor = factor * offset_n;
+                }
+                folded[i % n] = folded[i % n] + self.coefs[i] * factor;
+            }
+            
+            return ntt::coset_ntt(offset, omega, &folded);
+        }
+        
+        let mut values: Vec<F> = vec![];
+        
+        for d in domain.iter() {
+            values.push(self.evaluate(*d));
         }
         values
     }
     
-    pub fn interpolate_domain(domain: &Vec<FieldElement>, values: &Vec<FieldElement> ) -> Polynomial {
+    pub fn interpolate_domain(domain: &[F], values: &[F]) -> Polynomial<F> {
         assert!(domain.len() == values.len());
-        assert!(domain.len() > 0);
+        assert!(!domain.is_empty());
         
-        let mut acc = Polynomial::new(vec![FieldElement::new(0)]);
+        if let Some((offset, omega)) = Polynomial::coset_structure(domain) {
+            return Polynomial::new(ntt::coset_intt(offset, omega, values));
+        }
         
+        // barycentric weights 1 / prod_{j != i} (x_i - x_j), inverted in one batch
+        let mut denominators: Vec<F> = vec![];
         for i in 0..domain.len() {
-            let mut prod = Polynomial::new(vec![values[i]]);
-            
+            let mut denominator = F::one();
             for j in 0..domain.len() {
-                let x  = Polynomial::new(vec![FieldElement::zero(), FieldElement::one()]);
-                
+                if j != i {
+                    denominator = denominator * (domain[i] - domain[j]);
+                }
+            }
+            denominators.push(denominator);
+        }
+        let weights = F::batch_inverse(&denominators);
+        
+        let x  = Polynomial::new(vec![F::zero(), F::one()]);
+        let mut acc = Polynomial::new(vec![F::new(0)]);
+        
+        for i in 0..domain.len() {
+            let mut prod = Polynomial::new(vec![values[i] * weights[i]]);
+            
+            for (j, d) in domain.iter().enumerate() {
                 if j == i {
                     continue;
                 }
                 
-                prod = prod * ( (x - Polynomial::new(vec![domain[j]])) * Polynomial::new(vec![ (domain[i]-domain[j]).inverse()]) );
+                prod = prod * (x.clone() - Polynomial::new(vec![*d]));
             }
             acc = acc + prod; 
         }
         acc
     }
     
-    pub fn scale(&self, factor: FieldElement) -> Self{
+    pub fn scale(&self, factor: F) -> Self{
         
-        assert!(self.coefs.len() > 0);
+        assert!(!self.coefs.is_empty());
         
-        let mut scaled: Vec<FieldElement> = vec![];
+        let mut scaled: Vec<F> = vec![];
         
         for i in 0..self.coefs.len() {
             
             scaled.push ((factor ^ i as u128) * self.coefs[i]);
         }
         
-        return Polynomial::new(scaled);
+        Polynomial::new(scaled)
     }
     
-    pub fn test_colinearity(domain: Vec<FieldElement>, values: Vec<FieldElement> ) -> bool {
+    pub fn test_colinearity(domain: Vec<F>, values: Vec<F> ) -> bool {
         
         let polynomial = Polynomial::interpolate_domain(&domain, &values);
         
-        return polynomial.degree() <= 1;
+        polynomial.degree() <= 1
         
     }
 
-     pub fn zerofier_domain(domain: &Vec<FieldElement>) -> Polynomial {
+     pub fn zerofier_domain(domain: &[F]) -> Polynomial<F> {
         
-        let x = Polynomial::new(vec![FieldElement::zero(), FieldElement::one()]);
-        let mut acc = Polynomial::new(vec![FieldElement::one()]);
+        let x = Polynomial::new(vec![F::zero(), F::one()]);
+        let mut acc = Polynomial::new(vec![F::one()]);
         
-        for d in 0..domain.len() {
+        for d in domain.iter() {
             
-            acc = acc.clone() * (x.clone() - Polynomial::new(vec![domain[d]]));
+            acc = acc.clone() * (x.clone() - Polynomial::new(vec![*d]));
         }
         acc
     }
 }
     
-impl ops::Neg for Polynomial {
+impl<F: Field> ops::Neg for Polynomial<F> {
     
-    type Output = Polynomial;
+    type Output = Polynomial<F>;
     
-    fn neg(self) -> Polynomial {
+    fn neg(self) -> Polynomial<F> {
     
         let mut n = self.coefs.clone();
         
-        for i in 0..n.len() {
-            n[i] = -n[i];
+        for c in n.iter_mut() {
+            *c = -*c;
         }
         
-        return Polynomial::new(n);
+        Polynomial::new(n)
     }
 }
 
-impl ops::Add for Polynomial {
-    type Output = Polynomial;
+impl<F: Field> ops::Add for Polynomial<F> {
+    type Output = Polynomial<F>;
     
-    fn add(self, rhs: Polynomial) -> Polynomial {
+    fn add(self, rhs: Polynomial<F>) -> Polynomial<F> {
         
         if self.degree() == -1 {
             return rhs;
@@ -194,39 +255,39 @@ impl ops::Add for Polynomial {
             return self;
         }
         
-        let mut coeffs = vec![FieldElement::zero(); cmp::max(self.coefs.len(), rhs.coefs.len())];
+        let mut coeffs = vec![F::zero(); cmp::max(self.coefs.len(), rhs.coefs.len())];
         
-        for i in 0..self.coefs.len() {
-            coeffs[i] = coeffs[i] + self.coefs[i];
+        for (c, a) in coeffs.iter_mut().zip(self.coefs.iter()) {
+            *c = *c + *a;
         }
         
-        for i in 0..rhs.coefs.len() {
-            coeffs[i] = coeffs[i] + rhs.coefs[i];
+        for (c, b) in coeffs.iter_mut().zip(rhs.coefs.iter()) {
+            *c = *c + *b;
         }
         
-        return Polynomial::new(coeffs);
+        Polynomial::new(coeffs)
         
     }
 }
 
-impl ops::Sub for Polynomial {
-    type Output = Polynomial;
+impl<F: Field> ops::Sub for Polynomial<F> {
+    type Output = Polynomial<F>;
     
-    fn sub(self, rhs: Polynomial) -> Polynomial {
+    fn sub(self, rhs: Polynomial<F>) -> Polynomial<F> {
         
-        return self + -rhs;
+        self + -rhs
     }
 }
 
-impl ops::Mul for Polynomial {
-    type Output = Polynomial;
+impl<F: Field> ops::Mul for Polynomial<F> {
+    type Output = Polynomial<F>;
     
-    fn mul(self, rhs: Polynomial) -> Polynomial {
+    fn mul(self, rhs: Polynomial<F>) -> Polynomial<F> {
         
-        if self.coefs.len() == 0 || rhs.coefs.len() == 0 {
+        if self.coefs.is_empty() || rhs.coefs.is_empty() {
             return Polynomial::new(vec![]);
         }
-        let mut buf = vec![FieldElement::zero(); (self.coefs.len() + rhs.coefs.len() -1)];
+        let mut buf = vec![F::zero(); self.coefs.len() + rhs.coefs.len() -1];
         
         for i in 0..self.coefs.len() {
             if self.coefs[i].is_zero() {
@@ -238,20 +299,20 @@ impl ops::Mul for Polynomial {
             }
         }
         
-        return Polynomial::new(buf);
+        Polynomial::new(buf)
     }
 }
 
-impl ops::Mul<&Polynomial> for Polynomial {
-    type Output = Polynomial; 
+impl<F: Field> ops::Mul<&Polynomial<F>> for Polynomial<F> {
+    type Output = Polynomial<F>; 
     
      
-    fn mul(self, rhs: &Polynomial) -> Polynomial {
+    fn mul(self, rhs: &Polynomial<F>) -> Polynomial<F> {
         
-        if self.coefs.len() == 0 || rhs.coefs.len() == 0 {
+        if self.coefs.is_empty() || rhs.coefs.is_empty() {
             return Polynomial::new(vec![]);
         }
-        let mut buf = vec![FieldElement::zero(); (self.coefs.len() + rhs.coefs.len() -1)];
+        let mut buf = vec![F::zero(); self.coefs.len() + rhs.coefs.len() -1];
         
         for i in 0..self.coefs.len() {
             if self.coefs[i].is_zero() {
@@ -263,25 +324,25 @@ impl ops::Mul<&Polynomial> for Polynomial {
             }
         }
         
-        return Polynomial::new(buf);
+        Polynomial::new(buf)
     }
     
 }
 
-impl ops::Div for Polynomial {
-    type Output = Polynomial;
+impl<F: Field> ops::Div for Polynomial<F> {
+    type Output = Polynomial<F>;
     
-    fn div (self, rhs: Polynomial) -> Polynomial {
+    fn div (self, rhs: Polynomial<F>) -> Polynomial<F> {
         let (quo, rem) = self.divide(&rhs);
-        assert!(!rem.is_zero());
+        assert!(rem.is_zero());
         quo 
     }
 }
 
-impl ops::Rem for Polynomial {
-    type Output = Polynomial;
+impl<F: Field> ops::Rem for Polynomial<F> {
+    type Output = Polynomial<F>;
     
-    fn rem(self, rhs: Polynomial) -> Polynomial {
+    fn rem(self, rhs: Polynomial<F>) -> Polynomial<F> {
         let (_, rem) = self.divide(&rhs);
         rem
     }
@@ -289,39 +350,39 @@ impl ops::Rem for Polynomial {
 
 
 
-impl PartialEq for Polynomial  {
+impl<F: Field> PartialEq for Polynomial<F> {
     
     fn eq(&self, other: &Self) -> bool {
         if self.degree() != other.degree() {
             return false; 
         }
         if self.degree() == -1 {
-            return false;
+            return true;
         }
         
-        for i in 0..self.coefs.len() {
+        for i in 0..(self.degree() + 1) as usize {
             if self.coefs[i] != other.coefs[i] {
                 return false;
             }
         }
         
-        return true;
+        true
     }
     
 }
 
-impl ops::BitXor<u128> for Polynomial {
-    type Output = Polynomial;
+impl<F: Field> ops::BitXor<u128> for Polynomial<F> {
+    type Output = Polynomial<F>;
     
-    fn bitxor (self, exponent:u128) -> Polynomial {
+    fn bitxor (self, exponent:u128) -> Polynomial<F> {
+        if exponent == 0 {
+            return Polynomial::new(vec![F::one()]);
+        }
         if self.is_zero() {
             return Polynomial::new(vec![]);
         }
-        if exponent == 0 {
-            return Polynomial::new(vec![FieldElement::one()]);
-        }
         
-        let mut acc = Polynomial::new(vec![FieldElement::one()]);
+        let mut acc = Polynomial::new(vec![F::one()]);
         
         for i in (0..format!("{exponent:b}").to_string().chars().count()).rev() {
             
@@ -332,7 +393,7 @@ impl ops::BitXor<u128> for Polynomial {
             
         }
         
-        Polynomial::new(vec![])
+        acc
     }
 }
 
@@ -431,7 +492,7 @@ mod tests {
     #[test]
     pub fn test_zerofier() {
 
-        for trial in 0..10 {
+        for _ in 0..10 {
             let mut rng = rand::thread_rng();
 
             let degree:u8 = rng.gen();
@@ -445,7 +506,7 @@ mod tests {
                 OsRng.fill_bytes(&mut fake_index);
                 let new = FieldElement::sample(&fake_index);
 
-                domain.push(new.clone());
+                domain.push(new);
 
 
             }
@@ -454,9 +515,9 @@ mod tests {
 
             assert!(zerofier.degree()  == degree as i128, "zerofier has degree unequal to size of domain");
 
-            for d in 0..domain.len() {
+            for d in domain.iter() {
 
-                assert!(zerofier.evaluate(domain[d]) == FieldElement::zero(), "zerofier has degree unequal to size of domain");
+                assert!(zerofier.evaluate(*d) == FieldElement::zero(), "zerofier has degree unequal to size of domain");
             }
 
             let mut fake_index = [0u8;32];
@@ -465,11 +526,49 @@ mod tests {
             // Almost no chance of collision here
             let random = FieldElement::sample(&fake_index);
 
-            assert!(zerofier.evaluate(random.clone()) != FieldElement::zero(), "zerofier evaluates to zero where it should not");
+            assert!(zerofier.evaluate(random) != FieldElement::zero(), "zerofier evaluates to zero where it should not");
 
 
         }
         println!("univariate zerofier test success \\o/");
     }
 
+
+    #[test]
+    pub fn test_subgroup_domain() {
+
+        let n: usize = 1 << 12;
+        let omega = FieldElement::generator().primitive_nth_root(n as u128);
+        let offset = FieldElement::generator();
+
+        let mut coset: Vec<FieldElement> = vec![];
+        for i in 0..n {
+            coset.push(offset * (omega ^ i as u128));
+        }
+
+        let mut values: Vec<FieldElement> = vec![];
+        for _ in 0..n {
+            let mut bytes = [0u8;32];
+            OsRng.fill_bytes(&mut bytes);
+            values.push(FieldElement::sample(&bytes));
+        }
+
+        // interpolation then evaluation on a large coset goes through the ntt
+        let poly = Polynomial::interpolate_domain(&coset, &values);
+        assert!(poly.evaluate_domain(&coset) == values, "fail subgroup domain test 1");
+
+        for i in (0..n).step_by(n / 8) {
+            assert!(poly.evaluate(coset[i]) == values[i], "fail subgroup domain test 2");
+        }
+
+        // polynomials of degree at least the domain size still evaluate correctly
+        let small = 16;
+        let domain: Vec<FieldElement> = coset.iter().step_by(n / small).cloned().collect();
+        let long = poly.clone() * Polynomial::new(vec![FieldElement::new(3), FieldElement::one()]);
+        let expected: Vec<FieldElement> = domain.iter().map(|d| long.evaluate(*d)).collect();
+        assert!(long.evaluate_domain(&domain) == expected, "fail subgroup domain test 3");
+
+        println!("univariate subgroup domain success \\o/");
+    }
+
 }
\ No newline at end of file